@@ -1,11 +1,8 @@
-use crate::{
-    consts::{MINING_REWARD, NEEDED_HASH_START},
-    util::{sha256, time_since_unix_epoch},
-};
+use crate::util::{sha256, time_since_unix_epoch};
 use rand::random;
 use serde::{Deserialize, Serialize};
 
-use super::{Blockchain, Transaction};
+use super::{BlockError, Blockchain, Branch, ChainParams, Op, Transaction, TransactionError};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Block {
@@ -16,15 +13,32 @@ pub struct Block {
     pub children: Vec<Block>,
 }
 
-// we can't check with the children in the hash
-#[derive(Serialize)]
-struct BlockForCheck {
+/// the part of a block which gets hashed, miners only need the header to search for a nonce
+/// (we can't check with the children in the hash)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BlockHeader {
     pub prev_hash: Vec<u8>,
-    pub transactions: Vec<Transaction>,
+    pub transactions_hash: Vec<u8>,
     pub date: u128,
     pub nonce: u64,
 }
 
+impl BlockHeader {
+    pub fn hash(&self) -> Vec<u8> {
+        sha256(&bincode::serialize(self).unwrap())
+    }
+
+    /// whether the hash starts with the given number of null bytes
+    pub fn meets_difficulty(&self, difficulty: usize) -> bool {
+        self.hash().iter().take_while(|byte| **byte == 0).count() >= difficulty
+    }
+
+    /// whether the hash meets the difficulty of the network
+    pub fn verify_nonce(&self, params: &ChainParams) -> bool {
+        self.meets_difficulty(params.difficulty)
+    }
+}
+
 impl Block {
     pub fn new(prev_hash: Vec<u8>, transactions: Vec<Transaction>) -> Self {
         Self {
@@ -36,56 +50,301 @@ impl Block {
         }
     }
 
+    pub fn header(&self) -> BlockHeader {
+        BlockHeader {
+            prev_hash: self.prev_hash.clone(),
+            transactions_hash: sha256(&bincode::serialize(&self.transactions).unwrap()),
+            date: self.date,
+            nonce: self.nonce,
+        }
+    }
+
     pub fn hash(&self) -> Vec<u8> {
-        sha256(
-            &bincode::serialize(&BlockForCheck {
-                prev_hash: self.prev_hash.clone(),
-                transactions: self.transactions.clone(),
-                date: self.date,
-                nonce: self.nonce,
-            })
-            .unwrap(),
-        )
+        self.header().hash()
     }
 
-    pub fn verify_nonce(&self) -> bool {
-        self.hash().starts_with(&NEEDED_HASH_START)
+    /// the coinbase (last transaction) carries the extra nonce in its input's unlocking script after the height,
+    /// giving the miner a new nonce space when the block's nonces are exhausted
+    pub fn set_extra_nonce(&mut self, extra_nonce: u64) {
+        if let Some(coinbase) = self.transactions.last_mut() {
+            let ops = &mut coinbase.transaction_inputs[0].unlocking_script.ops;
+            ops.truncate(1);
+            ops.push(Op::Push(extra_nonce.to_be_bytes().to_vec()));
+        }
+    }
+
+    pub fn verify_nonce(&self, params: &ChainParams) -> bool {
+        self.header().verify_nonce(params)
     }
 
-    pub fn verify(&self, prev_hash: &[u8], chain: &Blockchain) -> bool {
+    /// the branch ends with the block before this one (it's only restored if the block and its children are valid)
+    pub fn verify(
+        &self,
+        prev_hash: &[u8],
+        branch: &mut Branch,
+        chain: &Blockchain,
+    ) -> Result<(), BlockError> {
         let self_hash = self.hash();
+        let height = branch.height();
 
-        self.prev_hash == prev_hash
-            && self.verify_nonce()
-            && self
-                .transactions
-                .iter()
-                .take(self.transactions.len() - 1)
-                .all(|transaction| transaction.verify(chain))
-            && self.transactions.last().unwrap().transaction_outputs[0].amount == MINING_REWARD
-            && self
-                .children
-                .iter()
-                .all(|child| child.verify(&self_hash, chain))
-    }
-
-    pub fn push(&mut self, block: &Block, chain: &Blockchain) -> bool {
-        if block.prev_hash == self.hash() {
-            if block.verify(&self.hash(), chain) {
-                self.children.push(block.clone());
-                return true;
-            } else {
-                return false;
+        self.check_limits(&chain.params)?;
+
+        if self.prev_hash != prev_hash {
+            return Err(BlockError::WrongPrevHash);
+        }
+        if !chain.params.matches_checkpoint(height, &self_hash) {
+            return Err(BlockError::CheckpointMismatch { height });
+        }
+        if !self.verify_nonce(&chain.params) {
+            return Err(BlockError::BadProofOfWork);
+        }
+        self.verify_date(&branch.dates, chain)?;
+
+        // first, so a block without transactions is rejected before the others are checked
+        let (coinbase, transactions) = self.split_coinbase()?;
+        // so no two coinbases on a branch have the same hash
+        if coinbase.coinbase_height() != Some(height) {
+            return Err(BlockError::WrongCoinbaseHeight { height });
+        }
+
+        // transactions may spend the outputs of transactions before them in the block
+        for (index, transaction) in transactions.iter().enumerate() {
+            transaction
+                .verify_amounts(branch, &transactions[..index])
+                .map_err(|error| BlockError::InvalidTransaction { index, error })?;
+
+            // neither a block before it on the branch nor a transaction before it in the block spent them
+            for tx_in in &transaction.transaction_inputs {
+                let outpoint = (
+                    tx_in.prev_transaction_hash.clone(),
+                    tx_in.prev_transaction_index,
+                );
+                if !branch.spent.insert(outpoint) {
+                    return Err(BlockError::InvalidTransaction {
+                        index,
+                        error: TransactionError::AlreadySpent,
+                    });
+                }
             }
-        } else {
-            for child in &mut self.children {
-                if child.push(block, chain) {
-                    return true;
+
+            if !transaction.spends_mature_outputs(branch, &chain.params) {
+                return Err(BlockError::InvalidTransaction {
+                    index,
+                    error: TransactionError::ImmatureCoinbase,
+                });
+            }
+
+            transaction
+                .verify_locks(branch, &chain.params)
+                .map_err(|error| BlockError::InvalidTransaction { index, error })?;
+        }
+
+        // the transactions don't spend more than they have, so there are fees unless they overflow
+        let subsidy = chain.params.emission_schedule.subsidy(height);
+        let allowed = self
+            .fees(branch)
+            .and_then(|fees| subsidy.checked_add(fees))
+            .ok_or(BlockError::FeesOverflow)?;
+        let paid = coinbase
+            .tx_outs_sum()
+            .ok_or(BlockError::InvalidTransaction {
+                index: transactions.len(),
+                error: TransactionError::AmountOverflow,
+            })?;
+        if paid > allowed {
+            return Err(BlockError::CoinbaseOverpays { paid, allowed });
+        }
+
+        // the expensive part last, all at once
+        if chain.checks_signatures(&self_hash) {
+            self.verify_scripts(chain, branch)?;
+        }
+
+        branch.push(self);
+        self.children
+            .iter()
+            .try_for_each(|child| child.verify(&self_hash, branch, chain))?;
+        branch.pop(self);
+
+        Ok(())
+    }
+
+    /// the outputs all transactions except the coinbase spend
+    pub fn spent_outpoints(&self) -> impl Iterator<Item = (Vec<u8>, u32)> + '_ {
+        self.transactions
+            .split_last()
+            .map_or(&[][..], |(_, transactions)| transactions)
+            .iter()
+            .flat_map(|transaction| &transaction.transaction_inputs)
+            .map(|tx_in| {
+                (
+                    tx_in.prev_transaction_hash.clone(),
+                    tx_in.prev_transaction_index,
+                )
+            })
+    }
+
+    /// the scripts of all transactions except the coinbase
+    fn verify_scripts(&self, chain: &Blockchain, branch: &Branch) -> Result<(), BlockError> {
+        let transactions = &self.transactions[..self.transactions.len() - 1];
+        let spent_outputs = transactions
+            .iter()
+            .enumerate()
+            .map(|(index, transaction)| {
+                transaction
+                    .spent_outputs(branch, &transactions[..index])
+                    .map_err(|error| BlockError::InvalidTransaction { index, error })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        chain
+            .signature_cache
+            .verify(transactions.iter().zip(spent_outputs))
+            .map_err(|(index, error)| BlockError::InvalidTransaction { index, error })
+    }
+
+    /// the date has to be after the median of the last blocks' dates,
+    /// but not too far ahead of the network's time
+    fn verify_date(&self, prev_dates: &[u128], chain: &Blockchain) -> Result<(), BlockError> {
+        let median_time_past = median_time_past(prev_dates, &chain.params);
+
+        if self.date <= median_time_past {
+            return Err(BlockError::TooOld { median_time_past });
+        }
+        if self.date > chain.network_time.now() + chain.params.max_future_block_time as u128 * 1000
+        {
+            return Err(BlockError::TooFarInFuture);
+        }
+
+        Ok(())
+    }
+
+    /// the size of the header and the transactions (bincode encoded), the children don't count
+    pub fn size(&self) -> usize {
+        (bincode::serialized_size(&self.header()).unwrap()
+            + bincode::serialized_size(&self.transactions).unwrap()) as usize
+    }
+
+    pub fn sigops(&self) -> usize {
+        self.transactions
+            .iter()
+            .map(|transaction| transaction.sigops())
+            .sum()
+    }
+
+    /// the consensus limits of the block and its transactions
+    pub fn check_limits(&self, params: &ChainParams) -> Result<(), BlockError> {
+        if self.size() > params.max_block_size {
+            return Err(BlockError::TooBig {
+                max: params.max_block_size,
+            });
+        }
+        if self.sigops() > params.max_block_sigops {
+            return Err(BlockError::TooManySigops {
+                max: params.max_block_sigops,
+            });
+        }
+
+        self.transactions
+            .iter()
+            .enumerate()
+            .try_for_each(|(index, transaction)| {
+                transaction
+                    .check_limits(params)
+                    .map_err(|error| BlockError::InvalidTransaction { index, error })
+            })
+    }
+
+    /// the fees of all transactions except the coinbase,
+    /// None if a transaction spends more than it has or the amounts overflow
+    pub fn fees(&self, branch: &Branch) -> Option<u32> {
+        let transactions = &self.transactions[..self.transactions.len() - 1];
+
+        transactions
+            .iter()
+            .enumerate()
+            .try_fold(0u32, |fees, (i, transaction)| {
+                let fee = transaction
+                    .tx_ins_sum(branch, &transactions[..i])?
+                    .checked_sub(transaction.tx_outs_sum()?)?;
+
+                fees.checked_add(fee)
+            })
+    }
+
+    /// the last transaction and only the last one is the coinbase,
+    /// it may pay out the subsidy and the fees to any number of outputs
+    fn split_coinbase(&self) -> Result<(&Transaction, &[Transaction]), BlockError> {
+        let (coinbase, transactions) = match self.transactions.split_last() {
+            Some(split) if split.0.is_coinbase() => split,
+            _ => return Err(BlockError::NoCoinbase),
+        };
+
+        if transactions
+            .iter()
+            .any(|transaction| transaction.is_coinbase())
+        {
+            return Err(BlockError::ExtraCoinbase);
+        }
+
+        Ok((coinbase, transactions))
+    }
+
+    /// the branch ends with the block before this one
+    ///
+    /// fails with UnknownParent if the block before it is neither this one nor below it
+    pub fn push(
+        &mut self,
+        block: &Block,
+        branch: &mut Branch,
+        chain: &Blockchain,
+    ) -> Result<(), BlockError> {
+        branch.push(self);
+
+        let result = if block.prev_hash == self.hash() {
+            let height = branch.height();
+            let block_hash = block.hash();
+
+            if self.children.iter().any(|child| child.hash() == block_hash) {
+                Err(BlockError::AlreadyKnown)
+            } else if let Some(checkpoint_height) = chain
+                .last_checkpoint_height()
+                .filter(|checkpoint_height| height <= *checkpoint_height)
+            {
+                // the main chain can't change below a checkpoint it reached
+                Err(BlockError::ForkBelowCheckpoint { checkpoint_height })
+            } else {
+                let verified = block.verify(&self.hash(), branch, chain);
+                if verified.is_ok() {
+                    self.children.push(block.clone());
                 }
+                verified
             }
+        } else {
+            self.children
+                .iter_mut()
+                .map(|child| child.push(block, branch, chain))
+                .find(|result| *result != Err(BlockError::UnknownParent))
+                .unwrap_or(Err(BlockError::UnknownParent))
+        };
+
+        branch.pop(self);
+
+        result
+    }
+
+    /// the hashes from this block down to the block with the hash, None if it's not below this one
+    pub fn path_to(&self, hash: &[u8]) -> Option<Vec<Vec<u8>>> {
+        let self_hash = self.hash();
+
+        if self_hash == hash {
+            return Some(vec![self_hash]);
         }
 
-        false
+        let mut path = self.children.iter().find_map(|child| child.path_to(hash))?;
+        path.insert(0, self_hash);
+
+        Some(path)
     }
 
     pub fn get_longest_chain(&self) -> Vec<Block> {
@@ -121,3 +380,243 @@ impl Block {
         all_blocks
     }
 }
+
+/// the median of the last median_time_span dates, 0 without any
+pub fn median_time_past(dates: &[u128], params: &ChainParams) -> u128 {
+    let mut dates = dates[dates.len().saturating_sub(params.median_time_span)..].to_vec();
+    dates.sort_unstable();
+
+    dates.get(dates.len() / 2).copied().unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use lazy_static::lazy_static;
+    use rand::rngs::OsRng;
+    use rsa::RsaPrivateKey;
+
+    use crate::util::to_hex;
+
+    use super::super::{BlockTemplateBuilder, Script, TransactionInput, TransactionOutput, Wallet};
+    use super::*;
+
+    lazy_static! {
+        // a small key, generating it is slow in debug builds
+        static ref WALLET: Wallet = {
+            let private_key = RsaPrivateKey::new(&mut OsRng, 512).unwrap();
+            let public_key = private_key.to_public_key();
+
+            Wallet {
+                private_key,
+                public_key,
+            }
+        };
+    }
+
+    /// every hash solves a block on regtest, the genesis block pays to the wallet
+    fn chain() -> Blockchain {
+        let mut chain = Blockchain::new(ChainParams::regtest(), WALLET.public_key.clone());
+        chain.compute_utxos();
+
+        chain
+    }
+
+    /// the next block on the main chain, with the unmined transactions
+    fn next_block(chain: &Blockchain) -> Block {
+        BlockTemplateBuilder::new(WALLET.public_key.clone())
+            .build(chain)
+            .unwrap()
+    }
+
+    fn payment(amount: u32, transaction_fee: u32, chain: &mut Blockchain) -> Transaction {
+        Transaction::new(
+            amount,
+            transaction_fee,
+            Some(WALLET.clone()),
+            Script::pay_to_public_key(&WALLET.public_key),
+            0,
+            None,
+            chain,
+        )
+        .unwrap()
+    }
+
+    /// spends the first output of the transaction
+    fn spending(hash: Vec<u8>, amount: u32) -> Transaction {
+        let mut transaction = Transaction {
+            version: 1,
+            transaction_inputs: vec![TransactionInput::new(hash, 0)],
+            transaction_outputs: vec![TransactionOutput::new(amount, WALLET.public_key.clone())],
+            lock_time: 0,
+        };
+        transaction.sign(&WALLET.private_key);
+
+        transaction
+    }
+
+    fn coinbase(block: &mut Block) -> &mut Transaction {
+        block.transactions.last_mut().unwrap()
+    }
+
+    #[test]
+    fn the_coinbase_pays_at_most_the_subsidy_and_the_fees() {
+        let mut chain = chain();
+        let transaction = payment(10, 3, &mut chain);
+        chain.push_unmined_transaction(transaction).unwrap();
+        let allowed = chain.params.emission_schedule.subsidy(1) + 3;
+
+        let mut block = next_block(&chain);
+        assert_eq!(coinbase(&mut block).tx_outs_sum(), Some(allowed));
+        coinbase(&mut block).transaction_outputs[0].amount += 1;
+        assert_eq!(
+            chain.push_block(block),
+            Err(BlockError::CoinbaseOverpays {
+                paid: allowed + 1,
+                allowed
+            })
+        );
+
+        assert_eq!(chain.push_block(next_block(&chain)), Ok(()));
+    }
+
+    #[test]
+    fn overflowing_amounts_are_rejected() {
+        let mut chain = chain();
+
+        // the outputs of a transaction
+        let mut transaction = payment(10, 0, &mut chain);
+        transaction
+            .transaction_outputs
+            .push(TransactionOutput::new(u32::MAX, WALLET.public_key.clone()));
+        transaction.sign(&WALLET.private_key);
+        let mut block = next_block(&chain);
+        block.transactions.insert(0, transaction);
+        assert_eq!(
+            chain.push_block(block),
+            Err(BlockError::InvalidTransaction {
+                index: 0,
+                error: TransactionError::AmountOverflow
+            })
+        );
+
+        // the outputs of the coinbase
+        let mut block = next_block(&chain);
+        coinbase(&mut block)
+            .transaction_outputs
+            .push(TransactionOutput::new(u32::MAX, WALLET.public_key.clone()));
+        assert_eq!(
+            chain.push_block(block),
+            Err(BlockError::InvalidTransaction {
+                index: 0,
+                error: TransactionError::AmountOverflow
+            })
+        );
+    }
+
+    #[test]
+    fn coinbases_mature_after_coinbase_maturity_blocks() {
+        let mut chain = chain();
+        chain.params.coinbase_maturity = 3;
+
+        let mut block = next_block(&chain);
+        let coinbase_hash = coinbase(&mut block).hash();
+        let amount = coinbase(&mut block).tx_outs_sum().unwrap();
+        chain.push_block(block).unwrap();
+
+        // at the heights 2 and 3 it's immature, at 4 it's mature
+        for _ in 2..4 {
+            let mut block = next_block(&chain);
+            block
+                .transactions
+                .insert(0, spending(coinbase_hash.clone(), amount));
+            assert_eq!(
+                chain.push_block(block),
+                Err(BlockError::InvalidTransaction {
+                    index: 0,
+                    error: TransactionError::ImmatureCoinbase
+                })
+            );
+
+            chain.push_block(next_block(&chain)).unwrap();
+        }
+
+        let mut block = next_block(&chain);
+        block
+            .transactions
+            .insert(0, spending(coinbase_hash, amount));
+        assert_eq!(chain.push_block(block), Ok(()));
+    }
+
+    #[test]
+    fn coinbases_carry_the_height_of_their_block() {
+        let mut chain = chain();
+
+        let mut first = next_block(&chain);
+        let first_hash = coinbase(&mut first).hash();
+        chain.push_block(first).unwrap();
+        let mut second = next_block(&chain);
+        // the same outputs, but another hash
+        assert_ne!(coinbase(&mut second).hash(), first_hash);
+
+        coinbase(&mut second).transaction_inputs[0]
+            .unlocking_script
+            .ops[0] = Op::Push(1u64.to_be_bytes().to_vec());
+        assert_eq!(
+            chain.push_block(second),
+            Err(BlockError::WrongCoinbaseHeight { height: 2 })
+        );
+    }
+
+    #[test]
+    fn blocks_have_to_match_the_checkpoints() {
+        let mut chain = chain();
+        let block = next_block(&chain);
+        let mut fork = block.clone();
+        fork.nonce += 1;
+
+        chain.params.checkpoints.insert(1, "00".repeat(32));
+        assert_eq!(
+            chain.push_block(block.clone()),
+            Err(BlockError::CheckpointMismatch { height: 1 })
+        );
+
+        chain.params.checkpoints.insert(1, to_hex(&block.hash()));
+        assert_eq!(chain.push_block(block), Ok(()));
+        // once it's reached the chain can't fork below it
+        assert_eq!(
+            chain.push_block(fork),
+            Err(BlockError::ForkBelowCheckpoint {
+                checkpoint_height: 1
+            })
+        );
+    }
+
+    #[test]
+    fn the_median_time_past_is_the_median_of_the_last_dates() {
+        let mut params = ChainParams::regtest();
+        params.median_time_span = 3;
+
+        assert_eq!(median_time_past(&[], &params), 0);
+        assert_eq!(median_time_past(&[7, 1], &params), 7);
+        assert_eq!(median_time_past(&[9, 5, 1, 4, 2, 3], &params), 3);
+    }
+
+    #[test]
+    fn blocks_have_to_be_after_the_median_time_past() {
+        let mut chain = chain();
+        for _ in 0..3 {
+            chain.push_block(next_block(&chain)).unwrap();
+        }
+        let median_time_past = chain.main_branch().median_time_past(&chain.params);
+
+        let mut block = next_block(&chain);
+        block.date = median_time_past;
+        assert_eq!(
+            chain.push_block(block.clone()),
+            Err(BlockError::TooOld { median_time_past })
+        );
+
+        block.date = median_time_past + 1;
+        assert_eq!(chain.push_block(block), Ok(()));
+    }
+}
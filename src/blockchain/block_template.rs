@@ -0,0 +1,152 @@
+use log::debug;
+use rsa::RsaPublicKey;
+
+use super::{Block, Blockchain, Transaction, TransactionOutput};
+
+/// builds the blocks a miner works on from the unmined transactions of the chain
+pub struct BlockTemplateBuilder {
+    payee: RsaPublicKey,
+}
+
+impl BlockTemplateBuilder {
+    pub fn new(payee: RsaPublicKey) -> Self {
+        Self { payee }
+    }
+
+    /// build a block on top of the main chain, taking the transactions with the highest fee rate first
+    ///
+    /// a transaction is only taken once all transactions it spends from are in the block
+    ///
+    /// returns None if there is no chain to mine on yet
+    pub fn build(&self, chain: &Blockchain) -> Option<Block> {
+        self.build_with_payouts(chain, &[])
+    }
+
+    /// like build, but the subsidy and the fees are split between the payees by their weights,
+    /// the rest goes to our payee
+    pub fn build_with_payouts(
+        &self,
+        chain: &Blockchain,
+        payouts: &[(RsaPublicKey, u64)],
+    ) -> Option<Block> {
+        let prev_hash = chain.main_chain().last()?.hash();
+        let branch = chain.main_branch();
+
+        let mut candidates: Vec<_> = chain
+            .unmined_transactions
+            .iter()
+            .filter_map(|transaction| {
+                let fee = transaction
+                    .tx_ins_sum(&branch, &chain.unmined_transactions)?
+                    .checked_sub(transaction.tx_outs_sum()?)?;
+
+                Some((
+                    transaction.clone(),
+                    fee,
+                    fee as f64 / transaction.size() as f64,
+                ))
+            })
+            .collect();
+
+        candidates.sort_by(|(_, _, a), (_, _, b)| b.partial_cmp(a).unwrap());
+
+        // reserve space for a coinbase paying out to everyone, with an extra nonce
+        let mut reserved = Block::new(
+            prev_hash.clone(),
+            vec![Transaction::new_coinbase(
+                chain.next_height(),
+                payouts
+                    .iter()
+                    .map(|(payee, _)| payee)
+                    .chain([&self.payee])
+                    .map(|payee| TransactionOutput::new(0, payee.clone()))
+                    .collect(),
+            )],
+        );
+        reserved.set_extra_nonce(0);
+        let mut block_size = reserved.size();
+        let mut sigops = 0;
+
+        let mut transactions: Vec<Transaction> = vec![];
+        let mut fees = 0;
+        // what the coinbase pays out, it can't overflow
+        let mut total = chain.params.emission_schedule.subsidy(chain.next_height());
+
+        while let Some(i) = candidates.iter().position(|(transaction, fee, _)| {
+            total.checked_add(*fee).is_some()
+                && block_size + transaction.size() <= chain.params.max_block_size
+                && sigops + transaction.sigops() <= chain.params.max_block_sigops
+                && chain.spends_unspent_outputs(transaction, &transactions)
+                && spends_nothing_twice(transaction, &transactions)
+        }) {
+            let (transaction, fee, _) = candidates.remove(i);
+
+            block_size += transaction.size();
+            sigops += transaction.sigops();
+            fees += fee;
+            total += fee;
+
+            transactions.push(transaction);
+        }
+
+        debug!(
+            "Built a block template with {} transactions, {} bytes and {} fees",
+            transactions.len(),
+            block_size,
+            fees
+        );
+
+        // the coinbase is always the last transaction
+        transactions.push(Transaction::new_coinbase(
+            chain.next_height(),
+            self.coinbase_outputs(total, payouts),
+        ));
+
+        let mut block = Block::new(prev_hash, transactions);
+        block.date = chain.network_time.now().max(chain.median_time_past() + 1);
+
+        Some(block)
+    }
+
+    fn coinbase_outputs(
+        &self,
+        total: u32,
+        payouts: &[(RsaPublicKey, u64)],
+    ) -> Vec<TransactionOutput> {
+        let total_weight: u64 = payouts.iter().map(|(_, weight)| weight).sum();
+
+        let mut outputs = vec![];
+        let mut paid = 0;
+
+        for (payee, weight) in payouts {
+            let amount = (total as u64 * weight)
+                .checked_div(total_weight)
+                .unwrap_or(0) as u32;
+
+            if amount > 0 {
+                outputs.push(TransactionOutput::new(amount, payee.clone()));
+                paid += amount;
+            }
+        }
+
+        // whatever can't be split evenly
+        if total > paid {
+            outputs.push(TransactionOutput::new(total - paid, self.payee.clone()));
+        }
+
+        outputs
+    }
+}
+
+/// whether the transaction spends none of the outputs the other transactions already spend
+fn spends_nothing_twice(transaction: &Transaction, others: &[Transaction]) -> bool {
+    transaction.transaction_inputs.iter().all(|tx_in| {
+        others
+            .iter()
+            .flat_map(|other| other.transaction_inputs.iter())
+            .all(|other_tx_in| {
+                other_tx_in.prev_transaction_hash != tx_in.prev_transaction_hash
+                    || other_tx_in.prev_transaction_index != tx_in.prev_transaction_index
+            })
+    })
+}
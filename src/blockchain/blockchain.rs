@@ -1,28 +1,53 @@
+use std::{collections::HashSet, mem, sync::Arc};
+
+use log::info;
 use rsa::RsaPublicKey;
 use serde::{Deserialize, Serialize};
 
-use crate::consts::INITIAL_COIN_AMOUNT;
+use crate::util::from_hex;
 
-use super::{Block, Transaction, TransactionOutput};
+use super::{
+    block::median_time_past, Block, BlockError, Branch, ChainParams, NetworkTime, Script,
+    SignatureCache, Transaction, TransactionError, TransactionOutput,
+};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Blockchain {
     pub chain: Option<Block>,
     pub unmined_transactions: Vec<Transaction>,
     pub utxos: Vec<(Vec<u8>, u32, TransactionOutput)>,
+    // every node brings its own, so a peer can't change the rules
+    #[serde(skip)]
+    pub params: ChainParams,
+    #[serde(skip)]
+    pub network_time: NetworkTime,
+    // shared with the clones, so a block's signatures are only verified once
+    #[serde(skip)]
+    pub signature_cache: Arc<SignatureCache>,
+    // while syncing below an assume-valid block, signatures are only checked once the whole chain arrived
+    #[serde(skip)]
+    syncing: bool,
+    // while the chain arrives from the server, nothing should be built on top of it
+    #[serde(skip)]
+    receiving: bool,
+    // the assume-valid block and its ancestors, their signatures are never checked
+    #[serde(skip)]
+    assumed_valid: HashSet<Vec<u8>>,
 }
 
 impl Blockchain {
-    pub fn new(initial_payee_public_key: RsaPublicKey) -> Self {
-        let mut blockchain = Self::new_empty();
+    pub fn new(params: ChainParams, initial_payee_public_key: RsaPublicKey) -> Self {
+        let mut blockchain = Self::new_empty(params);
 
         blockchain.chain = Some(Block::new(
             vec![],
             vec![Transaction::new(
-                INITIAL_COIN_AMOUNT,
+                blockchain.params.initial_coin_amount,
+                0,
+                None,
+                Script::pay_to_public_key(&initial_payee_public_key),
                 0,
                 None,
-                initial_payee_public_key,
                 &mut blockchain,
             )
             .unwrap()],
@@ -31,41 +56,223 @@ impl Blockchain {
         blockchain
     }
 
-    pub fn new_empty() -> Self {
+    /// a chain starting with the genesis block of a genesis spec
+    pub fn new_from_genesis(params: ChainParams, genesis: Block) -> Result<Self, BlockError> {
+        if !params.accepts_genesis(&genesis) {
+            return Err(BlockError::WrongGenesis {
+                network: params.name,
+            });
+        }
+
+        let mut blockchain = Self::new_empty(params);
+        blockchain.chain = Some(genesis);
+
+        Ok(blockchain)
+    }
+
+    pub fn new_empty(params: ChainParams) -> Self {
         Self {
             chain: None,
             unmined_transactions: vec![],
             utxos: vec![],
+            params,
+            network_time: NetworkTime::default(),
+            signature_cache: Arc::default(),
+            syncing: false,
+            receiving: false,
+            assumed_valid: HashSet::new(),
         }
     }
 
-    pub fn verify(&self) -> bool {
+    pub fn verify(&self) -> Result<(), BlockError> {
         if let Some(root) = &self.chain {
-            // we can't verify the root block, so we verify its children manually
+            // we can't verify the root block, only compare it with the pinned one,
+            // so we verify its children manually
+            if !self.params.accepts_genesis(root) {
+                return Err(self.wrong_genesis());
+            }
+
             let root_hash = root.hash();
+            let mut branch = Branch::default();
+            branch.push(root);
             root.children
                 .iter()
-                .all(|child| child.verify(&root_hash, &self))
+                .try_for_each(|child| child.verify(&root_hash, &mut branch, self))
         } else {
-            true
+            Ok(())
+        }
+    }
+
+    /// skip the signature checks of the arriving blocks if there is an assume-valid block,
+    /// verify checks them after finish_sync
+    pub fn start_sync(&mut self) {
+        self.receiving = true;
+        self.syncing = self.params.assume_valid.is_some();
+    }
+
+    /// check all signatures again, except the ones of the assume-valid block and its ancestors
+    pub fn finish_sync(&mut self) {
+        self.syncing = false;
+
+        let assume_valid = match &self.params.assume_valid {
+            Some(assume_valid) => assume_valid,
+            None => return,
+        };
+
+        let path = from_hex(assume_valid)
+            .ok()
+            .zip(self.chain.as_ref())
+            .and_then(|(hash, root)| root.path_to(&hash));
+
+        match path {
+            Some(path) => self.assumed_valid = path.into_iter().collect(),
+            None => info!("The assume-valid block isn't in the chain, checking all signatures"),
         }
     }
 
-    pub fn push_block(&mut self, block: Block) -> bool {
+    /// the blocks and unmined transactions of the server all arrived
+    pub fn finish_receiving(&mut self) {
+        self.receiving = false;
+    }
+
+    /// whether the server is still sending its chain, the miner waits for the whole chain
+    pub fn is_receiving(&self) -> bool {
+        self.receiving
+    }
+
+    pub fn checks_signatures(&self, block_hash: &[u8]) -> bool {
+        !self.syncing && !self.assumed_valid.contains(block_hash)
+    }
+
+    /// the height of the last checkpoint the main chain reached
+    pub fn last_checkpoint_height(&self) -> Option<u64> {
+        self.params
+            .checkpoints
+            .range(..self.next_height())
+            .next_back()
+            .map(|(height, _)| *height)
+    }
+
+    /// returns why the block was rejected, if it was
+    pub fn push_block(&mut self, block: Block) -> Result<(), BlockError> {
         let chain_clone = self.clone();
 
-        let success = if let Some(root) = &mut self.chain {
-            root.push(&block, &chain_clone)
-        } else {
+        if let Some(root) = &mut self.chain {
+            root.push(&block, &mut Branch::default(), &chain_clone)?;
+        } else if self.params.accepts_genesis(&block) {
             self.chain = Some(block);
-            true
-        };
+        } else {
+            return Err(self.wrong_genesis());
+        }
+
+        self.compute_utxos();
+        self.prune_unmined_transactions();
+
+        Ok(())
+    }
 
-        if success {
-            self.compute_utxos();
+    fn wrong_genesis(&self) -> BlockError {
+        BlockError::WrongGenesis {
+            network: self.params.name.clone(),
         }
+    }
+
+    /// add a transaction to the ones waiting to be mined, if it's standard and only spends unspent outputs,
+    /// returns why it was rejected otherwise
+    pub fn push_unmined_transaction(
+        &mut self,
+        transaction: Transaction,
+    ) -> Result<(), TransactionError> {
+        let branch = self.main_branch();
+
+        transaction.check_standard(&self.params)?;
+        transaction.verify(self, &branch, &self.unmined_transactions)?;
+
+        if !self.spends_unspent_outputs(&transaction, &self.unmined_transactions) {
+            return Err(TransactionError::AlreadySpent);
+        }
+        if !transaction.spends_mature_outputs(&branch, &self.params) {
+            return Err(TransactionError::ImmatureCoinbase);
+        }
+        // it has to fit into the next block
+        transaction.verify_locks(&branch, &self.params)?;
+
+        self.unmined_transactions.push(transaction);
+        Ok(())
+    }
+
+    pub fn contains_unmined_transaction(&self, hash: &[u8]) -> bool {
+        self.unmined_transactions
+            .iter()
+            .any(|transaction| transaction.hash() == hash)
+    }
+
+    /// whether all inputs spend unspent outputs of the main chain or outputs of the pending transactions
+    pub fn spends_unspent_outputs(
+        &self,
+        transaction: &Transaction,
+        pending: &[Transaction],
+    ) -> bool {
+        transaction.transaction_inputs.iter().all(|tx_in| {
+            let spends = |hash: &Vec<u8>, index: u32| {
+                *hash == tx_in.prev_transaction_hash && index == tx_in.prev_transaction_index
+            };
+
+            self.utxos
+                .iter()
+                .any(|(hash, index, _)| spends(hash, *index))
+                || pending.iter().any(|pending_transaction| {
+                    let hash = pending_transaction.hash();
+                    (0..pending_transaction.transaction_outputs.len() as u32)
+                        .any(|index| spends(&hash, index))
+                })
+        })
+    }
+
+    /// drop the unmined transactions which got mined or can't be mined anymore after the main chain changed
+    fn prune_unmined_transactions(&mut self) {
+        let mined: Vec<_> = self
+            .main_chain()
+            .iter()
+            .flat_map(|block| {
+                block
+                    .transactions
+                    .iter()
+                    .map(|transaction| transaction.hash())
+            })
+            .collect();
+
+        let branch = self.main_branch();
+        let mut kept = vec![];
 
-        success
+        for transaction in mem::take(&mut self.unmined_transactions) {
+            if !mined.contains(&transaction.hash())
+                && self.spends_unspent_outputs(&transaction, &kept)
+                && transaction.spends_mature_outputs(&branch, &self.params)
+                && transaction.verify_locks(&branch, &self.params).is_ok()
+            {
+                kept.push(transaction);
+            }
+        }
+
+        self.unmined_transactions = kept;
+    }
+
+    /// the date the next block on the main chain has to be after
+    pub fn median_time_past(&self) -> u128 {
+        let dates: Vec<_> = self.main_chain().iter().map(|block| block.date).collect();
+
+        median_time_past(&dates, &self.params)
+    }
+
+    /// the height of the next block on the main chain
+    pub fn next_height(&self) -> u64 {
+        self.main_chain().len() as u64
+    }
+
+    /// the main chain as a branch, what the next block on it builds on
+    pub fn main_branch(&self) -> Branch {
+        Branch::new(&self.main_chain())
     }
 
     pub fn main_chain(&self) -> Vec<Block> {
@@ -76,6 +283,13 @@ impl Blockchain {
         }
     }
 
+    /// the block with the hash, without its children
+    pub fn find_block(&self, hash: &[u8]) -> Option<Block> {
+        self.all_blocks()
+            .into_iter()
+            .find(|block| block.hash() == hash)
+    }
+
     pub fn all_blocks(&self) -> Vec<Block> {
         if let Some(root) = &self.chain {
             let mut result = vec![];
@@ -0,0 +1,90 @@
+use std::collections::{HashMap, HashSet};
+
+use super::{block::median_time_past, Block, ChainParams, Transaction};
+
+/// the blocks from the root down to a block, what the next block on the branch builds on,
+/// so the blocks of the other branches can't count for it
+#[derive(Debug, Clone, Default)]
+pub struct Branch {
+    // the dates of the blocks, the oldest first
+    pub dates: Vec<u128>,
+    // the outputs the blocks spent
+    pub spent: HashSet<(Vec<u8>, u32)>,
+    // the transactions of the blocks by hash, with the height of their block
+    transactions: HashMap<Vec<u8>, (Transaction, u64)>,
+}
+
+impl Branch {
+    /// the branch of the blocks, the root first
+    pub fn new(blocks: &[Block]) -> Self {
+        let mut branch = Self::default();
+
+        for block in blocks {
+            branch.push(block);
+        }
+
+        branch
+    }
+
+    /// the height of the next block on the branch
+    pub fn height(&self) -> u64 {
+        self.dates.len() as u64
+    }
+
+    /// the date the next block on the branch has to be after
+    pub fn median_time_past(&self, params: &ChainParams) -> u128 {
+        median_time_past(&self.dates, params)
+    }
+
+    /// add the block at the end of the branch
+    pub fn push(&mut self, block: &Block) {
+        let height = self.height();
+
+        self.dates.push(block.date);
+        self.spent.extend(block.spent_outpoints());
+        for transaction in &block.transactions {
+            self.transactions
+                .insert(transaction.hash(), (transaction.clone(), height));
+        }
+    }
+
+    /// remove the block at the end of the branch
+    pub fn pop(&mut self, block: &Block) {
+        self.dates.pop();
+        for outpoint in block.spent_outpoints() {
+            self.spent.remove(&outpoint);
+        }
+        for transaction in &block.transactions {
+            self.transactions.remove(&transaction.hash());
+        }
+    }
+
+    pub fn transaction(&self, hash: &[u8]) -> Option<&Transaction> {
+        self.transactions
+            .get(hash)
+            .map(|(transaction, _)| transaction)
+    }
+
+    /// whether the outputs of the transaction can be spent in the next block on the branch
+    ///
+    /// coinbase outputs only after coinbase_maturity blocks, so they can't vanish with an orphaned block,
+    /// the coins of the root right away
+    pub fn is_spendable(&self, hash: &[u8], params: &ChainParams) -> bool {
+        match self.transactions.get(hash) {
+            Some((transaction, height)) if *height > 0 && transaction.is_coinbase() => {
+                self.height() >= height + params.coinbase_maturity
+            }
+            _ => true,
+        }
+    }
+
+    /// the height of the block containing the transaction and the median time past before that block
+    pub fn confirmation(&self, hash: &[u8], params: &ChainParams) -> Option<(u64, u128)> {
+        let (_, height) = self.transactions.get(hash)?;
+
+        Some((
+            *height,
+            median_time_past(&self.dates[..*height as usize], params),
+        ))
+    }
+}
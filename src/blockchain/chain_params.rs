@@ -0,0 +1,150 @@
+use std::{collections::BTreeMap, fs::read_to_string, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::util::to_hex;
+
+use super::{Block, EmissionSchedule};
+
+/// the consensus rules of a network, all nodes of a network need the same ones
+///
+/// a file with chain parameters only needs the ones differing from mainnet
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChainParams {
+    pub name: String,
+    /// the number of null bytes a block's hash has to start with
+    pub difficulty: usize,
+    /// the hash of the genesis block (hex encoded), nodes only accept a chain starting with it
+    ///
+    /// the named networks don't pin one (every operator creates their own genesis block),
+    /// only a parameters file can, without it a syncing node trusts its server's genesis block
+    pub genesis_hash: Option<String>,
+    /// the hashes of the main chain's blocks at some heights (hex encoded),
+    /// blocks at these heights need the hash and forks below the last one reached are rejected
+    pub checkpoints: BTreeMap<u64, String>,
+    /// signatures of this block (hex encoded) and its ancestors aren't checked when syncing
+    pub assume_valid: Option<String>,
+    /// the coins of a genesis block created without a genesis spec
+    pub initial_coin_amount: u32,
+    pub emission_schedule: EmissionSchedule,
+    /// how many blocks have to be mined on top of a coinbase before its outputs can be spent
+    pub coinbase_maturity: u64,
+    /// a block's date has to be after the median date of this many blocks before it
+    pub median_time_span: usize,
+    /// how many seconds a block's date may be ahead of the network's time
+    pub max_future_block_time: u64,
+    /// the biggest block (its header and transactions, bincode encoded)
+    pub max_block_size: usize,
+    /// how many signatures the transactions of a block may need to check
+    pub max_block_sigops: usize,
+    pub max_transaction_size: usize,
+    pub max_transaction_inputs: usize,
+    pub max_transaction_outputs: usize,
+    /// the bits of new wallet keys
+    pub key_pair_length: usize,
+    /// the genesis node doesn't mine, blocks are only mined with the generate command
+    pub generate_on_demand: bool,
+}
+
+impl ChainParams {
+    pub fn mainnet() -> Self {
+        Self {
+            name: "mainnet".to_string(),
+            difficulty: 2,
+            genesis_hash: None,
+            checkpoints: BTreeMap::new(),
+            assume_valid: None,
+            initial_coin_amount: 100,
+            // 50, halving every 100000 blocks, but at least 1
+            emission_schedule: EmissionSchedule {
+                initial_subsidy: 50,
+                halving_interval: 100_000,
+                tail_emission: 1,
+            },
+            coinbase_maturity: 100,
+            median_time_span: 11,
+            max_future_block_time: 2 * 60 * 60,
+            max_block_size: 1024 * 1024,
+            max_block_sigops: 2000,
+            max_transaction_size: 512 * 1024,
+            max_transaction_inputs: 1000,
+            // enough for a pool paying out to every share of its window
+            max_transaction_outputs: 2000,
+            key_pair_length: 2048,
+            generate_on_demand: false,
+        }
+    }
+
+    /// like mainnet, but coins are worthless and mature faster
+    pub fn testnet() -> Self {
+        Self {
+            name: "testnet".to_string(),
+            initial_coin_amount: 1_000_000,
+            coinbase_maturity: 10,
+            ..Self::mainnet()
+        }
+    }
+
+    /// for local tests: every hash solves a block and blocks are only mined on demand
+    pub fn regtest() -> Self {
+        Self {
+            name: "regtest".to_string(),
+            difficulty: 0,
+            initial_coin_amount: 1_000_000,
+            emission_schedule: EmissionSchedule {
+                initial_subsidy: 50,
+                halving_interval: 150,
+                tail_emission: 0,
+            },
+            // small keys are generated much faster
+            key_pair_length: 1024,
+            generate_on_demand: true,
+            ..Self::mainnet()
+        }
+    }
+
+    /// the parameters of a network by its name, or from a json file
+    pub fn from_network(network: &str) -> Result<Self, String> {
+        match network {
+            "mainnet" => Ok(Self::mainnet()),
+            "testnet" => Ok(Self::testnet()),
+            "regtest" => Ok(Self::regtest()),
+            path => Self::from_file(Path::new(path)),
+        }
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let params = read_to_string(path).map_err(|err| {
+            format!(
+                "{:?} is neither mainnet, testnet, regtest nor a readable file: {}",
+                path, err
+            )
+        })?;
+
+        serde_json::from_str(&params)
+            .map_err(|err| format!("{:?} has no valid chain parameters: {}", path, err))
+    }
+
+    /// whether a block at the height can have the hash
+    pub fn matches_checkpoint(&self, height: u64, hash: &[u8]) -> bool {
+        match self.checkpoints.get(&height) {
+            Some(checkpoint) => to_hex(hash).eq_ignore_ascii_case(checkpoint),
+            None => true,
+        }
+    }
+
+    /// whether a chain may start with the block
+    pub fn accepts_genesis(&self, block: &Block) -> bool {
+        match &self.genesis_hash {
+            Some(hash) => to_hex(&block.hash()).eq_ignore_ascii_case(hash),
+            None => true,
+        }
+    }
+}
+
+impl Default for ChainParams {
+    fn default() -> Self {
+        Self::mainnet()
+    }
+}
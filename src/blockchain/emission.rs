@@ -0,0 +1,69 @@
+use serde::{Deserialize, Serialize};
+
+/// how many new coins a block may create, depending on its height
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct EmissionSchedule {
+    pub initial_subsidy: u32,
+    /// the subsidy halves every halving_interval blocks
+    pub halving_interval: u64,
+    /// the subsidy never drops below the tail emission, 0 caps the supply
+    pub tail_emission: u32,
+}
+
+impl EmissionSchedule {
+    pub fn subsidy(&self, height: u64) -> u32 {
+        let halvings = height.checked_div(self.halving_interval).unwrap_or(0);
+
+        let subsidy = if halvings < u32::BITS as u64 {
+            self.initial_subsidy >> halvings
+        } else {
+            0
+        };
+
+        subsidy.max(self.tail_emission)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCHEDULE: EmissionSchedule = EmissionSchedule {
+        initial_subsidy: 50,
+        halving_interval: 100,
+        tail_emission: 0,
+    };
+
+    #[test]
+    fn the_subsidy_halves_every_interval() {
+        assert_eq!(SCHEDULE.subsidy(0), 50);
+        assert_eq!(SCHEDULE.subsidy(99), 50);
+        assert_eq!(SCHEDULE.subsidy(100), 25);
+        assert_eq!(SCHEDULE.subsidy(199), 25);
+        assert_eq!(SCHEDULE.subsidy(200), 12);
+        assert_eq!(SCHEDULE.subsidy(600), 0);
+        assert_eq!(SCHEDULE.subsidy(u64::MAX), 0);
+    }
+
+    #[test]
+    fn the_subsidy_never_drops_below_the_tail_emission() {
+        let schedule = EmissionSchedule {
+            tail_emission: 3,
+            ..SCHEDULE
+        };
+
+        assert_eq!(schedule.subsidy(200), 12);
+        assert_eq!(schedule.subsidy(400), 3);
+        assert_eq!(schedule.subsidy(u64::MAX), 3);
+    }
+
+    #[test]
+    fn without_an_interval_it_never_halves() {
+        let schedule = EmissionSchedule {
+            halving_interval: 0,
+            ..SCHEDULE
+        };
+
+        assert_eq!(schedule.subsidy(u64::MAX), 50);
+    }
+}
@@ -0,0 +1,113 @@
+use std::{fs::read_to_string, path::Path};
+
+use rsa::{pkcs8::FromPublicKey, RsaPublicKey};
+use serde::{Deserialize, Serialize};
+
+use super::{transaction::MAX_COINBASE_DATA_SIZE, Block, Op, Transaction, TransactionOutput};
+
+/// everything the genesis block is built from, so every node builds the same one
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GenesisSpec {
+    /// milliseconds since the unix epoch
+    pub date: u128,
+    pub nonce: u64,
+    /// any text up to MAX_COINBASE_DATA_SIZE bytes, it's stored in the input of the genesis transaction
+    pub message: String,
+    pub allocations: Vec<GenesisAllocation>,
+}
+
+/// coins the genesis block pays to someone
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GenesisAllocation {
+    /// the PEM-encoded public key of the payee
+    pub payee: String,
+    pub amount: u32,
+}
+
+impl GenesisSpec {
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let spec = read_to_string(path)
+            .map_err(|err| format!("Failed to read the genesis spec from {:?}: {}", path, err))?;
+
+        serde_json::from_str(&spec)
+            .map_err(|err| format!("{:?} is no valid genesis spec: {}", path, err))
+    }
+
+    /// the genesis block has a single transaction paying out all allocations
+    pub fn build(&self) -> Result<Block, String> {
+        if self.allocations.is_empty() {
+            return Err("the genesis spec doesn't allocate any coins".to_string());
+        }
+        // it has to fit into the coinbase, so the genesis block's hash covers it
+        if self.message.len() > MAX_COINBASE_DATA_SIZE {
+            return Err(format!(
+                "the genesis message is longer than {} bytes",
+                MAX_COINBASE_DATA_SIZE
+            ));
+        }
+
+        let transaction_outputs = self
+            .allocations
+            .iter()
+            .map(|allocation| {
+                let payee = RsaPublicKey::from_public_key_pem(&allocation.payee)
+                    .map_err(|err| format!("an allocation has a wrong payee: {}", err))?;
+
+                Ok(TransactionOutput::new(allocation.amount, payee))
+            })
+            .collect::<Result<_, String>>()?;
+
+        let mut transaction = Transaction::new_coinbase(0, transaction_outputs);
+        transaction.transaction_inputs[0]
+            .unlocking_script
+            .ops
+            .push(Op::Push(self.message.as_bytes().to_vec()));
+
+        let mut block = Block::new(vec![], vec![transaction]);
+        block.date = self.date;
+        block.nonce = self.nonce;
+
+        Ok(block)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::OsRng;
+    use rsa::{pkcs8::ToPublicKey, RsaPrivateKey};
+
+    use super::*;
+
+    fn spec(message: String) -> GenesisSpec {
+        let key = RsaPrivateKey::new(&mut OsRng, 512).unwrap();
+
+        GenesisSpec {
+            date: 0,
+            nonce: 0,
+            message,
+            allocations: vec![GenesisAllocation {
+                payee: key.to_public_key().to_public_key_pem().unwrap(),
+                amount: 100,
+            }],
+        }
+    }
+
+    #[test]
+    fn the_hash_covers_the_message() {
+        let block = spec("a".repeat(MAX_COINBASE_DATA_SIZE)).build().unwrap();
+        assert!(block.transactions[0].is_coinbase());
+
+        let mut changed = block.clone();
+        changed.transactions[0].transaction_inputs[0]
+            .unlocking_script
+            .ops[1] = Op::Push(b"b".repeat(MAX_COINBASE_DATA_SIZE));
+        assert_ne!(changed.hash(), block.hash());
+    }
+
+    #[test]
+    fn long_messages_are_rejected() {
+        assert!(spec("a".repeat(MAX_COINBASE_DATA_SIZE + 1))
+            .build()
+            .is_err());
+    }
+}
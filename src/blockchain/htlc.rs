@@ -0,0 +1,215 @@
+use std::{
+    fs::{read_to_string, write},
+    path::Path,
+};
+
+use rsa::{
+    pkcs8::{FromPublicKey, ToPublicKey},
+    RsaPrivateKey, RsaPublicKey,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::util::{from_hex, sha256, to_hex};
+
+use super::{
+    lock_time::{RELATIVE_LOCK_TIME_VERSION, SEQUENCE_FINAL, SEQUENCE_LOCK_TIME_ONLY},
+    script::{sign, HTLC_PREIMAGE_SIZE},
+    Op, Script, Transaction, TransactionInput, TransactionOutput,
+};
+
+/// a hash time-locked contract: an output the recipient can spend by revealing the preimage of the hash,
+/// or the sender once the timeout passed
+///
+/// the parties of an atomic swap lock their coins to the same hash on both chains,
+/// redeeming one reveals the preimage for the other
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Htlc {
+    /// hex-encoded sha256 hash
+    hash: String,
+    // PEM-encoded
+    recipient: String,
+    sender: String,
+    /// a block height or unix timestamp, like a transaction's lock time
+    pub timeout: u64,
+    /// the locked output, hex-encoded (the txid doesn't cover the signatures,
+    /// so it doesn't change before the transaction is mined)
+    transaction_hash: String,
+    pub index: u32,
+    pub amount: u32,
+}
+
+impl Htlc {
+    /// the contract of the transaction's output locked by Script::htlc
+    pub fn new(
+        hash: &[u8],
+        recipient: &RsaPublicKey,
+        sender: &RsaPublicKey,
+        timeout: u64,
+        transaction: &Transaction,
+    ) -> Result<Self, String> {
+        let locking_script = Script::htlc(hash, recipient, sender, timeout);
+        let (index, tx_out) = transaction
+            .transaction_outputs
+            .iter()
+            .enumerate()
+            .find(|(_, tx_out)| tx_out.locking_script == locking_script)
+            .ok_or("The transaction doesn't lock an output to the contract")?;
+
+        Ok(Self {
+            hash: to_hex(hash),
+            recipient: recipient.to_public_key_pem().unwrap(),
+            sender: sender.to_public_key_pem().unwrap(),
+            timeout,
+            transaction_hash: to_hex(&transaction.hash()),
+            index: index as u32,
+            amount: tx_out.amount,
+        })
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let htlc = read_to_string(path)
+            .map_err(|err| format!("Failed to read the HTLC from {:?}: {}", path, err))?;
+
+        let htlc: Self = serde_json::from_str(&htlc)
+            .map_err(|err| format!("{:?} is no valid HTLC: {}", path, err))?;
+        htlc.check()
+            .map_err(|err| format!("{:?} is no valid HTLC: {}", path, err))?;
+
+        Ok(htlc)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), String> {
+        write(path, serde_json::to_string_pretty(self).unwrap())
+            .map_err(|err| format!("Failed to write the HTLC to {:?}: {}", path, err))
+    }
+
+    fn check(&self) -> Result<(), String> {
+        if from_hex(&self.hash)?.len() != 32 {
+            return Err("the hash isn't a sha256 hash".to_string());
+        }
+        from_hex(&self.transaction_hash)?;
+        for key in [&self.recipient, &self.sender] {
+            RsaPublicKey::from_public_key_pem(key)
+                .map_err(|err| format!("a key is not PEM-encoded: {}", err))?;
+        }
+
+        Ok(())
+    }
+
+    pub fn hash(&self) -> Vec<u8> {
+        from_hex(&self.hash).unwrap()
+    }
+
+    pub fn recipient(&self) -> RsaPublicKey {
+        RsaPublicKey::from_public_key_pem(&self.recipient).unwrap()
+    }
+
+    pub fn sender(&self) -> RsaPublicKey {
+        RsaPublicKey::from_public_key_pem(&self.sender).unwrap()
+    }
+
+    pub fn transaction_hash(&self) -> Vec<u8> {
+        from_hex(&self.transaction_hash).unwrap()
+    }
+
+    /// the recipient's transaction taking the coins, it reveals the preimage
+    pub fn redeem(
+        &self,
+        preimage: &[u8],
+        sign_key: &RsaPrivateKey,
+        transaction_fee: u32,
+    ) -> Result<Transaction, String> {
+        if preimage.len() != HTLC_PREIMAGE_SIZE {
+            return Err(format!(
+                "The preimage has to be {} bytes",
+                HTLC_PREIMAGE_SIZE
+            ));
+        }
+        if sha256(preimage) != self.hash() {
+            return Err("The preimage doesn't match the hash".to_string());
+        }
+        if sign_key.to_public_key() != self.recipient() {
+            return Err("The key isn't the recipient's".to_string());
+        }
+
+        let mut transaction =
+            self.spending_transaction(self.recipient(), transaction_fee, 0, SEQUENCE_FINAL)?;
+        let signature = sign(&transaction.signature_hash(), sign_key);
+        transaction.transaction_inputs[0].unlocking_script = Script::new(vec![
+            Op::Push(signature),
+            Op::Push(preimage.to_vec()),
+            Op::number(1),
+        ]);
+
+        Ok(transaction)
+    }
+
+    /// the sender's transaction taking the coins back, it can only be mined after the timeout
+    pub fn refund(
+        &self,
+        sign_key: &RsaPrivateKey,
+        transaction_fee: u32,
+    ) -> Result<Transaction, String> {
+        if sign_key.to_public_key() != self.sender() {
+            return Err("The key isn't the sender's".to_string());
+        }
+
+        // the lock time only counts if the input isn't final
+        let mut transaction = self.spending_transaction(
+            self.sender(),
+            transaction_fee,
+            self.timeout,
+            SEQUENCE_LOCK_TIME_ONLY,
+        )?;
+        let signature = sign(&transaction.signature_hash(), sign_key);
+        transaction.transaction_inputs[0].unlocking_script =
+            Script::new(vec![Op::Push(signature), Op::number(0)]);
+
+        Ok(transaction)
+    }
+
+    fn spending_transaction(
+        &self,
+        payee: RsaPublicKey,
+        transaction_fee: u32,
+        lock_time: u64,
+        sequence: u32,
+    ) -> Result<Transaction, String> {
+        if transaction_fee >= self.amount {
+            return Err(format!(
+                "The fee has to be less than the {} eincoin of the HTLC",
+                self.amount
+            ));
+        }
+
+        let mut tx_in = TransactionInput::new(self.transaction_hash(), self.index);
+        tx_in.sequence = sequence;
+
+        Ok(Transaction {
+            version: RELATIVE_LOCK_TIME_VERSION,
+            transaction_inputs: vec![tx_in],
+            transaction_outputs: vec![TransactionOutput::new(self.amount - transaction_fee, payee)],
+            lock_time,
+        })
+    }
+
+    /// the preimage, if the transaction redeems the HTLC
+    pub fn extract_preimage(&self, transaction: &Transaction) -> Option<Vec<u8>> {
+        let transaction_hash = self.transaction_hash();
+        let hash = self.hash();
+
+        transaction
+            .transaction_inputs
+            .iter()
+            .filter(|tx_in| {
+                tx_in.prev_transaction_hash == transaction_hash
+                    && tx_in.prev_transaction_index == self.index
+            })
+            .find_map(|tx_in| match tx_in.unlocking_script.ops.as_slice() {
+                [Op::Push(_), Op::Push(preimage), Op::Push(_)] if sha256(preimage) == hash => {
+                    Some(preimage.clone())
+                }
+                _ => None,
+            })
+    }
+}
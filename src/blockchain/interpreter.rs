@@ -0,0 +1,830 @@
+use crate::util::sha256;
+
+use super::{
+    lock_time::{LOCK_TIME_THRESHOLD, RELATIVE_LOCK_TIME_VERSION, SEQUENCE_FINAL},
+    script::{decode_number, encode_number, verify_signature},
+    Op, RelativeLock, Script, ScriptError, Transaction,
+};
+
+// the limits keep the verification of a single input cheap
+pub const MAX_SCRIPT_SIZE: usize = 10_000;
+/// everything but pushes counts
+pub const MAX_SCRIPT_OPS: usize = 200;
+pub const MAX_STACK_SIZE: usize = 1000;
+/// big enough for the keys and signatures of 4096 bit RSA keys
+pub const MAX_ELEMENT_SIZE: usize = 1024;
+pub const MAX_MULTISIG_KEYS: usize = 20;
+
+/// the input whose scripts run
+pub struct ScriptContext<'a> {
+    pub transaction: &'a Transaction,
+    pub index: usize,
+    pub signature_hash: &'a [u8],
+}
+
+/// run the unlocking script, then the locking script on the stack it left,
+/// the input may spend the output if only true is left on the stack afterwards
+pub fn verify_script(
+    unlocking_script: &Script,
+    locking_script: &Script,
+    context: &ScriptContext,
+) -> Result<(), ScriptError> {
+    if !unlocking_script.is_push_only() {
+        return Err(ScriptError::UnlockingNotPushOnly);
+    }
+
+    let mut interpreter = Interpreter {
+        stack: vec![],
+        context,
+    };
+    interpreter.run(unlocking_script)?;
+    interpreter.run(locking_script)?;
+
+    // a clean stack, so nobody can add pushes to the unlocking script of a valid transaction
+    match interpreter.stack.as_slice() {
+        [top] if is_true(top) => Ok(()),
+        _ => Err(ScriptError::Failed),
+    }
+}
+
+/// anything but empty data or only zero bytes
+fn is_true(data: &[u8]) -> bool {
+    data.iter().any(|byte| *byte != 0)
+}
+
+fn boolean(value: bool) -> Vec<u8> {
+    if value {
+        vec![1]
+    } else {
+        vec![]
+    }
+}
+
+struct Interpreter<'a> {
+    stack: Vec<Vec<u8>>,
+    context: &'a ScriptContext<'a>,
+}
+
+impl Interpreter<'_> {
+    fn run(&mut self, script: &Script) -> Result<(), ScriptError> {
+        if script.size() > MAX_SCRIPT_SIZE {
+            return Err(ScriptError::TooBig {
+                max: MAX_SCRIPT_SIZE,
+            });
+        }
+
+        // whether the branches we are in are executed, the innermost last
+        let mut executing: Vec<bool> = vec![];
+        let mut ops = 0;
+
+        for op in &script.ops {
+            if !matches!(op, Op::Push(_)) {
+                ops += 1;
+                if ops > MAX_SCRIPT_OPS {
+                    return Err(ScriptError::TooManyOps {
+                        max: MAX_SCRIPT_OPS,
+                    });
+                }
+            }
+
+            let active = executing.iter().all(|executed| *executed);
+
+            match op {
+                Op::If | Op::NotIf => {
+                    let condition = active && is_true(&self.pop()?) == matches!(op, Op::If);
+                    executing.push(condition);
+                }
+                Op::Else => {
+                    let executed = executing
+                        .last_mut()
+                        .ok_or(ScriptError::UnbalancedConditional)?;
+                    *executed = !*executed;
+                }
+                Op::EndIf => {
+                    executing.pop().ok_or(ScriptError::UnbalancedConditional)?;
+                }
+                _ if !active => {}
+                op => self.execute(op)?,
+            }
+
+            if self.stack.len() > MAX_STACK_SIZE {
+                return Err(ScriptError::StackOverflow {
+                    max: MAX_STACK_SIZE,
+                });
+            }
+        }
+
+        if !executing.is_empty() {
+            return Err(ScriptError::UnbalancedConditional);
+        }
+
+        Ok(())
+    }
+
+    fn execute(&mut self, op: &Op) -> Result<(), ScriptError> {
+        match op {
+            Op::Push(data) => {
+                if data.len() > MAX_ELEMENT_SIZE {
+                    return Err(ScriptError::ElementTooBig {
+                        max: MAX_ELEMENT_SIZE,
+                    });
+                }
+                self.stack.push(data.clone());
+            }
+            Op::Dup => {
+                let top = self.stack.last().ok_or(ScriptError::StackUnderflow)?;
+                self.stack.push(top.clone());
+            }
+            Op::Drop => {
+                self.pop()?;
+            }
+            Op::Swap => {
+                let top = self.pop()?;
+                let second = self.pop()?;
+                self.stack.push(top);
+                self.stack.push(second);
+            }
+            Op::Size => {
+                let top = self.stack.last().ok_or(ScriptError::StackUnderflow)?;
+                self.stack.push(encode_number(top.len() as u64));
+            }
+            Op::Sha256 => {
+                let data = self.pop()?;
+                self.stack.push(sha256(&data));
+            }
+            Op::Equal | Op::EqualVerify => {
+                let equal = self.pop()? == self.pop()?;
+                self.verify_or_push(op == &Op::EqualVerify, equal, ScriptError::VerifyFailed)?;
+            }
+            Op::Verify => {
+                if !is_true(&self.pop()?) {
+                    return Err(ScriptError::VerifyFailed);
+                }
+            }
+            Op::CheckSig | Op::CheckSigVerify => {
+                let public_key = self.pop()?;
+                let signature = self.pop()?;
+                let valid = verify_signature(self.context.signature_hash, &signature, &public_key);
+                self.verify_or_push(op == &Op::CheckSigVerify, valid, ScriptError::BadSignature)?;
+            }
+            Op::CheckMultiSig | Op::CheckMultiSigVerify => {
+                let valid = self.check_multisig()?;
+                self.verify_or_push(
+                    op == &Op::CheckMultiSigVerify,
+                    valid,
+                    ScriptError::BadSignature,
+                )?;
+            }
+            Op::CheckLockTimeVerify => {
+                let lock_time = self.pop_number()?;
+                self.check_lock_time(lock_time)?;
+            }
+            Op::CheckSequenceVerify => {
+                let sequence =
+                    u32::try_from(self.pop_number()?).map_err(|_| ScriptError::InvalidNumber)?;
+                self.check_sequence(sequence)?;
+            }
+            Op::If | Op::NotIf | Op::Else | Op::EndIf => unreachable!(),
+        }
+
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Result<Vec<u8>, ScriptError> {
+        self.stack.pop().ok_or(ScriptError::StackUnderflow)
+    }
+
+    fn pop_number(&mut self) -> Result<u64, ScriptError> {
+        decode_number(&self.pop()?).ok_or(ScriptError::InvalidNumber)
+    }
+
+    /// the verify variants fail instead of pushing false
+    fn verify_or_push(
+        &mut self,
+        verify: bool,
+        value: bool,
+        error: ScriptError,
+    ) -> Result<(), ScriptError> {
+        if verify {
+            if !value {
+                return Err(error);
+            }
+        } else {
+            self.stack.push(boolean(value));
+        }
+
+        Ok(())
+    }
+
+    /// the stack has the signatures, their number, the keys and their number, the last on top,
+    /// the signatures have to be in the same order as the keys they belong to
+    fn check_multisig(&mut self) -> Result<bool, ScriptError> {
+        let key_count = self.pop_number()? as usize;
+        if key_count > MAX_MULTISIG_KEYS {
+            return Err(ScriptError::TooManyKeys {
+                max: MAX_MULTISIG_KEYS,
+            });
+        }
+        let mut public_keys = (0..key_count)
+            .map(|_| self.pop())
+            .collect::<Result<Vec<_>, _>>()?;
+        public_keys.reverse();
+
+        let signature_count = self.pop_number()? as usize;
+        if signature_count > key_count {
+            return Err(ScriptError::InvalidMultisig);
+        }
+        let mut signatures = (0..signature_count)
+            .map(|_| self.pop())
+            .collect::<Result<Vec<_>, _>>()?;
+        signatures.reverse();
+
+        // every key can only be used once
+        let mut public_keys = public_keys.iter();
+        Ok(signatures.iter().all(|signature| {
+            public_keys.any(|public_key| {
+                verify_signature(self.context.signature_hash, signature, public_key)
+            })
+        }))
+    }
+
+    /// the transaction's lock time has to be at least the one in the script, both heights or both times
+    fn check_lock_time(&self, lock_time: u64) -> Result<(), ScriptError> {
+        let transaction = self.context.transaction;
+
+        if (lock_time < LOCK_TIME_THRESHOLD) != (transaction.lock_time < LOCK_TIME_THRESHOLD)
+            || lock_time > transaction.lock_time
+            // the transaction's lock time doesn't count if the input is final
+            || transaction.transaction_inputs[self.context.index].sequence == SEQUENCE_FINAL
+        {
+            return Err(ScriptError::LockTime);
+        }
+
+        Ok(())
+    }
+
+    /// the input's relative lock time has to be at least the one in the script, both blocks or both times
+    fn check_sequence(&self, sequence: u32) -> Result<(), ScriptError> {
+        let required = match RelativeLock::from_sequence(sequence) {
+            Some(required) => required,
+            // a sequence without a relative lock time doesn't require anything
+            None => return Ok(()),
+        };
+
+        let transaction = self.context.transaction;
+        let relative_lock = RelativeLock::from_sequence(
+            transaction.transaction_inputs[self.context.index].sequence,
+        );
+
+        let satisfied = transaction.version >= RELATIVE_LOCK_TIME_VERSION
+            && match (required, relative_lock) {
+                (RelativeLock::Blocks(required), Some(RelativeLock::Blocks(blocks))) => {
+                    blocks >= required
+                }
+                (RelativeLock::Time(required), Some(RelativeLock::Time(units))) => {
+                    units >= required
+                }
+                _ => false,
+            };
+
+        if !satisfied {
+            return Err(ScriptError::Sequence);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lazy_static::lazy_static;
+    use rand::rngs::OsRng;
+    use rsa::{RsaPrivateKey, RsaPublicKey};
+
+    use super::super::{
+        lock_time::SEQUENCE_LOCK_TIME_ONLY,
+        script::{sign, HTLC_PREIMAGE_SIZE},
+        TransactionInput, TransactionOutput,
+    };
+    use super::*;
+
+    lazy_static! {
+        // small keys, generating them is slow in debug builds
+        static ref KEYS: Vec<RsaPrivateKey> = (0..3)
+            .map(|_| RsaPrivateKey::new(&mut OsRng, 512).unwrap())
+            .collect();
+    }
+
+    fn public_key(index: usize) -> RsaPublicKey {
+        KEYS[index].to_public_key()
+    }
+
+    fn transaction(version: u32, lock_time: u64, sequence: u32) -> Transaction {
+        let mut tx_in = TransactionInput::new(vec![0; 32], 0);
+        tx_in.sequence = sequence;
+
+        Transaction {
+            version,
+            transaction_inputs: vec![tx_in],
+            transaction_outputs: vec![TransactionOutput::new(1, public_key(0))],
+            lock_time,
+        }
+    }
+
+    fn signature(transaction: &Transaction, key: usize) -> Op {
+        Op::Push(sign(&transaction.signature_hash(), &KEYS[key]))
+    }
+
+    fn run_with(
+        transaction: &Transaction,
+        unlocking: Vec<Op>,
+        locking: Vec<Op>,
+    ) -> Result<(), ScriptError> {
+        let signature_hash = transaction.signature_hash();
+        let context = ScriptContext {
+            transaction,
+            index: 0,
+            signature_hash: &signature_hash,
+        };
+
+        verify_script(&Script::new(unlocking), &Script::new(locking), &context)
+    }
+
+    fn run(unlocking: Vec<Op>, locking: Vec<Op>) -> Result<(), ScriptError> {
+        run_with(&transaction(1, 0, SEQUENCE_FINAL), unlocking, locking)
+    }
+
+    #[test]
+    fn pay_to_public_key() {
+        let transaction = transaction(1, 0, SEQUENCE_FINAL);
+        let locking = Script::pay_to_public_key(&public_key(0)).ops;
+
+        assert_eq!(
+            run_with(
+                &transaction,
+                vec![signature(&transaction, 0)],
+                locking.clone()
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            run_with(
+                &transaction,
+                vec![signature(&transaction, 1)],
+                locking.clone()
+            ),
+            Err(ScriptError::Failed)
+        );
+        assert_eq!(
+            run_with(&transaction, vec![], locking),
+            Err(ScriptError::StackUnderflow)
+        );
+    }
+
+    fn transaction_with_amount(amount: u32) -> Transaction {
+        let mut transaction = transaction(1, 0, SEQUENCE_FINAL);
+        transaction.transaction_outputs[0].amount = amount;
+
+        transaction
+    }
+
+    #[test]
+    fn signatures_only_sign_their_transaction() {
+        let transaction = transaction(1, 0, SEQUENCE_FINAL);
+        let other = transaction_with_amount(2);
+
+        assert_eq!(
+            run_with(
+                &other,
+                vec![signature(&transaction, 0)],
+                Script::pay_to_public_key(&public_key(0)).ops
+            ),
+            Err(ScriptError::Failed)
+        );
+    }
+
+    #[test]
+    fn verify_variants_fail_instead_of_pushing_false() {
+        let transaction = transaction(1, 0, SEQUENCE_FINAL);
+
+        assert_eq!(
+            run_with(
+                &transaction,
+                vec![signature(&transaction, 1)],
+                vec![
+                    Op::public_key(&public_key(0)),
+                    Op::CheckSigVerify,
+                    Op::number(1)
+                ]
+            ),
+            Err(ScriptError::BadSignature)
+        );
+        assert_eq!(
+            run(
+                vec![Op::number(1), Op::number(2)],
+                vec![Op::EqualVerify, Op::number(1)]
+            ),
+            Err(ScriptError::VerifyFailed)
+        );
+    }
+
+    #[test]
+    fn only_true_may_be_left_on_the_stack() {
+        let transaction = transaction(1, 0, SEQUENCE_FINAL);
+        let locking = Script::pay_to_public_key(&public_key(0)).ops;
+
+        assert_eq!(
+            run_with(
+                &transaction,
+                vec![Op::number(1), signature(&transaction, 0)],
+                locking
+            ),
+            Err(ScriptError::Failed)
+        );
+        assert_eq!(run(vec![], vec![Op::number(0)]), Err(ScriptError::Failed));
+        assert_eq!(run(vec![], vec![]), Err(ScriptError::Failed));
+        assert_eq!(run(vec![Op::Push(vec![0, 2])], vec![]), Ok(()));
+    }
+
+    #[test]
+    fn unlocking_scripts_only_push() {
+        assert_eq!(
+            run(vec![Op::number(1), Op::Dup, Op::Drop], vec![]),
+            Err(ScriptError::UnlockingNotPushOnly)
+        );
+    }
+
+    #[test]
+    fn multisig() {
+        let transaction = transaction(1, 0, SEQUENCE_FINAL);
+        let locking = Script::multisig(2, &[public_key(0), public_key(1), public_key(2)]).ops;
+
+        for (first, second) in [(0, 1), (0, 2), (1, 2)] {
+            assert_eq!(
+                run_with(
+                    &transaction,
+                    vec![
+                        signature(&transaction, first),
+                        signature(&transaction, second)
+                    ],
+                    locking.clone()
+                ),
+                Ok(())
+            );
+        }
+    }
+
+    #[test]
+    fn multisig_signatures_are_in_the_order_of_the_keys() {
+        let transaction = transaction(1, 0, SEQUENCE_FINAL);
+        let locking = Script::multisig(2, &[public_key(0), public_key(1), public_key(2)]).ops;
+
+        assert_eq!(
+            run_with(
+                &transaction,
+                vec![signature(&transaction, 2), signature(&transaction, 0)],
+                locking
+            ),
+            Err(ScriptError::Failed)
+        );
+    }
+
+    #[test]
+    fn multisig_keys_are_only_used_once() {
+        let transaction = transaction(1, 0, SEQUENCE_FINAL);
+        let locking = Script::multisig(2, &[public_key(0), public_key(1), public_key(2)]).ops;
+
+        assert_eq!(
+            run_with(
+                &transaction,
+                vec![signature(&transaction, 0), signature(&transaction, 0)],
+                locking
+            ),
+            Err(ScriptError::Failed)
+        );
+    }
+
+    #[test]
+    fn multisig_requires_at_most_all_keys() {
+        let transaction = transaction(1, 0, SEQUENCE_FINAL);
+        let mut locking = Script::multisig(2, &[public_key(0), public_key(1)]).ops;
+        locking[0] = Op::number(3);
+
+        assert_eq!(
+            run_with(
+                &transaction,
+                vec![
+                    signature(&transaction, 0),
+                    signature(&transaction, 1),
+                    signature(&transaction, 1)
+                ],
+                locking
+            ),
+            Err(ScriptError::InvalidMultisig)
+        );
+    }
+
+    #[test]
+    fn hash_lock() {
+        let preimage = b"preimage".to_vec();
+        let locking = vec![
+            Op::Sha256,
+            Op::Push(sha256(&preimage)),
+            Op::EqualVerify,
+            Op::number(1),
+        ];
+
+        assert_eq!(run(vec![Op::Push(preimage)], locking.clone()), Ok(()));
+        assert_eq!(
+            run(vec![Op::Push(b"guess".to_vec())], locking),
+            Err(ScriptError::VerifyFailed)
+        );
+    }
+
+    #[test]
+    fn size_keeps_the_element() {
+        assert_eq!(
+            run(
+                vec![Op::Push(vec![1, 2, 3])],
+                vec![Op::Size, Op::number(3), Op::EqualVerify]
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            run(vec![], vec![Op::Size]),
+            Err(ScriptError::StackUnderflow)
+        );
+    }
+
+    #[test]
+    fn htlc() {
+        let preimage = vec![7; HTLC_PREIMAGE_SIZE];
+        let locking = Script::htlc(&sha256(&preimage), &public_key(0), &public_key(1), 100).ops;
+
+        // the recipient with the preimage
+        let redeem = transaction(1, 0, SEQUENCE_FINAL);
+        assert_eq!(
+            run_with(
+                &redeem,
+                vec![
+                    signature(&redeem, 0),
+                    Op::Push(preimage.clone()),
+                    Op::number(1)
+                ],
+                locking.clone()
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            run_with(
+                &redeem,
+                vec![signature(&redeem, 1), Op::Push(preimage), Op::number(1)],
+                locking.clone()
+            ),
+            Err(ScriptError::Failed)
+        );
+
+        // the sender after the timeout
+        let refund = transaction(1, 100, SEQUENCE_LOCK_TIME_ONLY);
+        assert_eq!(
+            run_with(
+                &refund,
+                vec![signature(&refund, 1), Op::number(0)],
+                locking.clone()
+            ),
+            Ok(())
+        );
+        let early_refund = transaction(1, 99, SEQUENCE_LOCK_TIME_ONLY);
+        assert_eq!(
+            run_with(
+                &early_refund,
+                vec![signature(&early_refund, 1), Op::number(0)],
+                locking
+            ),
+            Err(ScriptError::LockTime)
+        );
+    }
+
+    #[test]
+    fn htlc_preimages_have_a_fixed_size() {
+        let redeem = transaction(1, 0, SEQUENCE_FINAL);
+
+        for preimage in [b"preimage".to_vec(), vec![7; HTLC_PREIMAGE_SIZE + 1]] {
+            let locking = Script::htlc(&sha256(&preimage), &public_key(0), &public_key(1), 100).ops;
+
+            assert_eq!(
+                run_with(
+                    &redeem,
+                    vec![signature(&redeem, 0), Op::Push(preimage), Op::number(1)],
+                    locking
+                ),
+                Err(ScriptError::VerifyFailed)
+            );
+        }
+    }
+
+    fn check_lock_time(lock_time: u64, sequence: u32, required: u64) -> Result<(), ScriptError> {
+        run_with(
+            &transaction(1, lock_time, sequence),
+            vec![],
+            vec![Op::number(required), Op::CheckLockTimeVerify, Op::number(1)],
+        )
+    }
+
+    #[test]
+    fn check_lock_time_verify() {
+        let time = LOCK_TIME_THRESHOLD + 1000;
+
+        assert_eq!(check_lock_time(10, SEQUENCE_LOCK_TIME_ONLY, 10), Ok(()));
+        assert_eq!(check_lock_time(11, SEQUENCE_LOCK_TIME_ONLY, 10), Ok(()));
+        assert_eq!(
+            check_lock_time(9, SEQUENCE_LOCK_TIME_ONLY, 10),
+            Err(ScriptError::LockTime)
+        );
+        assert_eq!(check_lock_time(time, SEQUENCE_LOCK_TIME_ONLY, time), Ok(()));
+        // heights and times can't be compared
+        assert_eq!(
+            check_lock_time(time, SEQUENCE_LOCK_TIME_ONLY, 10),
+            Err(ScriptError::LockTime)
+        );
+        assert_eq!(
+            check_lock_time(10, SEQUENCE_LOCK_TIME_ONLY, time),
+            Err(ScriptError::LockTime)
+        );
+        // the lock time doesn't count for final inputs
+        assert_eq!(
+            check_lock_time(10, SEQUENCE_FINAL, 10),
+            Err(ScriptError::LockTime)
+        );
+        assert_eq!(
+            run(
+                vec![],
+                vec![Op::Push(vec![1; 9]), Op::CheckLockTimeVerify, Op::number(1)]
+            ),
+            Err(ScriptError::InvalidNumber)
+        );
+    }
+
+    fn check_sequence(
+        version: u32,
+        relative_lock: Option<RelativeLock>,
+        required: RelativeLock,
+    ) -> Result<(), ScriptError> {
+        let sequence = relative_lock.map_or(SEQUENCE_FINAL, RelativeLock::to_sequence);
+
+        run_with(
+            &transaction(version, 0, sequence),
+            vec![],
+            vec![
+                Op::number(required.to_sequence() as u64),
+                Op::CheckSequenceVerify,
+                Op::number(1),
+            ],
+        )
+    }
+
+    #[test]
+    fn check_sequence_verify() {
+        use RelativeLock::{Blocks, Time};
+        let version = RELATIVE_LOCK_TIME_VERSION;
+
+        assert_eq!(
+            check_sequence(version, Some(Blocks(10)), Blocks(10)),
+            Ok(())
+        );
+        assert_eq!(
+            check_sequence(version, Some(Blocks(11)), Blocks(10)),
+            Ok(())
+        );
+        assert_eq!(check_sequence(version, Some(Time(5)), Time(5)), Ok(()));
+        assert_eq!(
+            check_sequence(version, Some(Blocks(9)), Blocks(10)),
+            Err(ScriptError::Sequence)
+        );
+        assert_eq!(
+            check_sequence(version, Some(Time(10)), Blocks(10)),
+            Err(ScriptError::Sequence)
+        );
+        assert_eq!(
+            check_sequence(version, None, Blocks(10)),
+            Err(ScriptError::Sequence)
+        );
+        // relative lock times aren't enforced before the version
+        assert_eq!(
+            check_sequence(version - 1, Some(Blocks(10)), Blocks(10)),
+            Err(ScriptError::Sequence)
+        );
+        // a sequence without a relative lock time doesn't require anything
+        assert_eq!(
+            run(
+                vec![],
+                vec![
+                    Op::number(SEQUENCE_FINAL as u64),
+                    Op::CheckSequenceVerify,
+                    Op::number(1)
+                ]
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn conditionals() {
+        let locking = vec![Op::If, Op::number(1), Op::Else, Op::number(0), Op::EndIf];
+
+        assert_eq!(run(vec![Op::number(1)], locking.clone()), Ok(()));
+        assert_eq!(run(vec![Op::number(0)], locking), Err(ScriptError::Failed));
+        assert_eq!(
+            run(
+                vec![Op::number(0)],
+                vec![Op::NotIf, Op::number(1), Op::EndIf]
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn unbalanced_conditionals() {
+        for locking in [
+            vec![Op::If, Op::number(1)],
+            vec![Op::number(1), Op::Else, Op::EndIf],
+            vec![Op::number(1), Op::EndIf],
+            vec![Op::If, Op::number(1), Op::EndIf, Op::EndIf],
+            vec![Op::If, Op::number(1), Op::Else, Op::number(1)],
+        ] {
+            assert_eq!(
+                run(vec![Op::number(1)], locking),
+                Err(ScriptError::UnbalancedConditional)
+            );
+        }
+    }
+
+    #[test]
+    fn script_size_limit() {
+        let pushes = vec![Op::Push(vec![0; 1000]); 10];
+
+        assert_eq!(
+            run(vec![], pushes),
+            Err(ScriptError::TooBig {
+                max: MAX_SCRIPT_SIZE
+            })
+        );
+    }
+
+    #[test]
+    fn script_ops_limit() {
+        let mut locking = vec![Op::number(1)];
+        locking.extend((0..MAX_SCRIPT_OPS / 2).flat_map(|_| [Op::Dup, Op::Drop]));
+        assert_eq!(run(vec![], locking.clone()), Ok(()));
+
+        locking.push(Op::Verify);
+        assert_eq!(
+            run(vec![], locking),
+            Err(ScriptError::TooManyOps {
+                max: MAX_SCRIPT_OPS
+            })
+        );
+    }
+
+    #[test]
+    fn stack_size_limit() {
+        // the stack the unlocking script leaves counts as well
+        let unlocking = vec![Op::number(0); MAX_STACK_SIZE / 2];
+        let locking = vec![Op::number(0); MAX_STACK_SIZE / 2 + 1];
+
+        assert_eq!(
+            run(unlocking, locking),
+            Err(ScriptError::StackOverflow {
+                max: MAX_STACK_SIZE
+            })
+        );
+    }
+
+    #[test]
+    fn element_size_limit() {
+        assert_eq!(
+            run(vec![Op::Push(vec![1; MAX_ELEMENT_SIZE])], vec![]),
+            Ok(())
+        );
+        assert_eq!(
+            run(vec![Op::Push(vec![1; MAX_ELEMENT_SIZE + 1])], vec![]),
+            Err(ScriptError::ElementTooBig {
+                max: MAX_ELEMENT_SIZE
+            })
+        );
+    }
+
+    #[test]
+    fn multisig_keys_limit() {
+        let mut locking = vec![Op::number(1)];
+        locking.extend(vec![Op::public_key(&public_key(0)); MAX_MULTISIG_KEYS + 1]);
+        locking.extend([Op::number(MAX_MULTISIG_KEYS as u64 + 1), Op::CheckMultiSig]);
+
+        assert_eq!(
+            run(vec![Op::number(1)], locking),
+            Err(ScriptError::TooManyKeys {
+                max: MAX_MULTISIG_KEYS
+            })
+        );
+    }
+}
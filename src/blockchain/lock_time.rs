@@ -0,0 +1,160 @@
+use std::{
+    fmt::{self, Display, Formatter},
+    str::FromStr,
+};
+
+/// lock times below are block heights, lock times from it on unix timestamps in seconds
+pub const LOCK_TIME_THRESHOLD: u64 = 500_000_000;
+/// the sequence of inputs without a relative lock time,
+/// the transaction's lock time only counts if an input has another one
+pub const SEQUENCE_FINAL: u32 = u32::MAX;
+/// the sequence of inputs without a relative lock time, which don't disable the lock time
+pub const SEQUENCE_LOCK_TIME_ONLY: u32 = SEQUENCE_FINAL - 1;
+/// relative lock times are only enforced from this transaction version on
+pub const RELATIVE_LOCK_TIME_VERSION: u32 = 2;
+
+// an input's sequence without this flag is its relative lock time
+const SEQUENCE_DISABLE_FLAG: u32 = 1 << 31;
+// with this flag the relative lock time is in units of 512 seconds, otherwise in blocks
+const SEQUENCE_TYPE_FLAG: u32 = 1 << 22;
+const SEQUENCE_VALUE_MASK: u32 = 0xffff;
+const SEQUENCE_GRANULARITY: u64 = 512;
+
+/// how long the output an input spends has to be in the chain before the input can be mined
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RelativeLock {
+    Blocks(u16),
+    /// in units of 512 seconds, compared with the median time past
+    Time(u16),
+}
+
+impl RelativeLock {
+    pub fn from_sequence(sequence: u32) -> Option<Self> {
+        if sequence & SEQUENCE_DISABLE_FLAG != 0 {
+            return None;
+        }
+
+        let value = (sequence & SEQUENCE_VALUE_MASK) as u16;
+        if sequence & SEQUENCE_TYPE_FLAG != 0 {
+            Some(RelativeLock::Time(value))
+        } else {
+            Some(RelativeLock::Blocks(value))
+        }
+    }
+
+    pub fn to_sequence(self) -> u32 {
+        match self {
+            RelativeLock::Blocks(blocks) => blocks as u32,
+            RelativeLock::Time(units) => SEQUENCE_TYPE_FLAG | units as u32,
+        }
+    }
+
+    /// whether an input with the lock can be mined in a block at the height with the median time past,
+    /// when the output it spends is in a block at confirmation_height
+    /// and confirmation_time is the median time past before that block
+    pub fn is_unlocked(
+        self,
+        height: u64,
+        median_time_past: u128,
+        confirmation_height: u64,
+        confirmation_time: u128,
+    ) -> bool {
+        match self {
+            RelativeLock::Blocks(blocks) => height >= confirmation_height + blocks as u64,
+            RelativeLock::Time(units) => {
+                median_time_past
+                    >= confirmation_time + (units as u64 * SEQUENCE_GRANULARITY * 1000) as u128
+            }
+        }
+    }
+}
+
+/// a number of blocks, or seconds with an s suffix (rounded up to multiples of 512)
+impl FromStr for RelativeLock {
+    type Err = String;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "{} is not a number of blocks or seconds (like 3600s)",
+                string
+            )
+        };
+
+        match string.strip_suffix('s') {
+            Some(seconds) => {
+                let seconds: u64 = seconds.parse().map_err(|_| invalid())?;
+                let units = u16::try_from(seconds.div_ceil(SEQUENCE_GRANULARITY))
+                    .map_err(|_| format!("{} seconds is too long", seconds))?;
+
+                Ok(RelativeLock::Time(units))
+            }
+            None => Ok(RelativeLock::Blocks(string.parse().map_err(|_| invalid())?)),
+        }
+    }
+}
+
+impl Display for RelativeLock {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            RelativeLock::Blocks(blocks) => write!(f, "{} blocks", blocks),
+            RelativeLock::Time(units) => {
+                write!(f, "{} seconds", *units as u64 * SEQUENCE_GRANULARITY)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UNIT: u128 = SEQUENCE_GRANULARITY as u128 * 1000;
+
+    #[test]
+    fn block_locks_unlock_at_the_height() {
+        let lock = RelativeLock::Blocks(10);
+
+        assert!(!lock.is_unlocked(14, 0, 5, 0));
+        assert!(lock.is_unlocked(15, 0, 5, 0));
+        assert!(RelativeLock::Blocks(0).is_unlocked(5, 0, 5, 0));
+    }
+
+    #[test]
+    fn time_locks_unlock_at_the_median_time_past() {
+        let lock = RelativeLock::Time(2);
+        let confirmation_time = 1_000_000;
+
+        assert!(!lock.is_unlocked(0, confirmation_time + 2 * UNIT - 1, 0, confirmation_time));
+        assert!(lock.is_unlocked(0, confirmation_time + 2 * UNIT, 0, confirmation_time));
+    }
+
+    #[test]
+    fn locks_survive_the_sequence() {
+        for lock in [
+            RelativeLock::Blocks(0),
+            RelativeLock::Blocks(u16::MAX),
+            RelativeLock::Time(1),
+            RelativeLock::Time(u16::MAX),
+        ] {
+            assert_eq!(RelativeLock::from_sequence(lock.to_sequence()), Some(lock));
+        }
+
+        assert_eq!(RelativeLock::from_sequence(SEQUENCE_FINAL), None);
+        assert_eq!(RelativeLock::from_sequence(SEQUENCE_LOCK_TIME_ONLY), None);
+        assert_eq!(RelativeLock::from_sequence(SEQUENCE_DISABLE_FLAG | 5), None);
+    }
+
+    #[test]
+    fn seconds_are_rounded_up_to_units() {
+        assert_eq!("10".parse(), Ok(RelativeLock::Blocks(10)));
+        assert_eq!("0s".parse(), Ok(RelativeLock::Time(0)));
+        assert_eq!("512s".parse(), Ok(RelativeLock::Time(1)));
+        assert_eq!("513s".parse(), Ok(RelativeLock::Time(2)));
+        assert!(format!("{}s", (u16::MAX as u64 + 1) * SEQUENCE_GRANULARITY)
+            .parse::<RelativeLock>()
+            .is_err());
+        assert!("s".parse::<RelativeLock>().is_err());
+        assert!("ten".parse::<RelativeLock>().is_err());
+    }
+}
@@ -1,13 +1,40 @@
 mod block;
+mod block_template;
 mod blockchain;
+mod branch;
+mod chain_params;
+mod emission;
+mod genesis_spec;
+mod htlc;
+mod interpreter;
+mod lock_time;
+mod multisig;
+mod network_time;
+mod partially_signed_transaction;
+mod script;
+mod signature_cache;
 mod transaction;
 mod transaction_input;
 mod transaction_output;
+mod validation_error;
 mod wallet;
 
-pub use block::Block;
+pub use block::{Block, BlockHeader};
+pub use block_template::BlockTemplateBuilder;
 pub use blockchain::Blockchain;
+pub use branch::Branch;
+pub use chain_params::ChainParams;
+pub use emission::EmissionSchedule;
+pub use genesis_spec::{GenesisAllocation, GenesisSpec};
+pub use htlc::Htlc;
+pub use lock_time::RelativeLock;
+pub use multisig::{payee_from_file, MultisigAddress, MultisigSpend};
+pub use network_time::NetworkTime;
+pub use partially_signed_transaction::PartiallySignedTransaction;
+pub use script::{Op, Script, HTLC_PREIMAGE_SIZE};
+pub use signature_cache::SignatureCache;
 pub use transaction::Transaction;
 pub use transaction_input::TransactionInput;
 pub use transaction_output::TransactionOutput;
+pub use validation_error::{BlockError, ScriptError, TransactionError};
 pub use wallet::Wallet;
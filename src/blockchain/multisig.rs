@@ -0,0 +1,357 @@
+use std::{
+    fs::{read_to_string, write},
+    path::Path,
+};
+
+use rsa::{
+    pkcs8::{FromPublicKey, ToPublicKey},
+    RsaPrivateKey, RsaPublicKey,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::util::checked_sum;
+
+use super::{
+    interpreter::MAX_MULTISIG_KEYS,
+    script::{sign, verify_signature},
+    Blockchain, Op, Script, Transaction, TransactionOutput,
+};
+
+/// coins sent to it can only be spent with signatures of required of the keys,
+/// the co-signers share it as a json file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MultisigAddress {
+    required: usize,
+    // PEM-encoded and sorted, so the address doesn't depend on the order the keys were given in
+    public_keys: Vec<String>,
+}
+
+impl MultisigAddress {
+    pub fn new(required: usize, public_keys: &[RsaPublicKey]) -> Result<Self, String> {
+        let mut public_keys: Vec<_> = public_keys
+            .iter()
+            .map(|key| key.to_public_key_pem().unwrap())
+            .collect();
+        public_keys.sort();
+
+        let address = Self {
+            required,
+            public_keys,
+        };
+        address.check()?;
+
+        Ok(address)
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let address = read_to_string(path).map_err(|err| {
+            format!(
+                "Failed to read the multisig address from {:?}: {}",
+                path, err
+            )
+        })?;
+
+        let address: Self = serde_json::from_str(&address)
+            .map_err(|err| format!("{:?} is no valid multisig address: {}", path, err))?;
+        address
+            .check()
+            .map_err(|err| format!("{:?} is no valid multisig address: {}", path, err))?;
+
+        Ok(address)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), String> {
+        write(path, serde_json::to_string_pretty(self).unwrap()).map_err(|err| {
+            format!(
+                "Failed to write the multisig address to {:?}: {}",
+                path, err
+            )
+        })
+    }
+
+    fn check(&self) -> Result<(), String> {
+        if self.public_keys.len() > MAX_MULTISIG_KEYS {
+            return Err(format!(
+                "a multisig address can't have more than {} keys",
+                MAX_MULTISIG_KEYS
+            ));
+        }
+        if self.required == 0 || self.required > self.public_keys.len() {
+            return Err(format!(
+                "{} of {} keys can't be required",
+                self.required,
+                self.public_keys.len()
+            ));
+        }
+        if self.public_keys.windows(2).any(|keys| keys[0] == keys[1]) {
+            return Err("a key is given more than once".to_string());
+        }
+        if self.public_keys.windows(2).any(|keys| keys[0] > keys[1]) {
+            return Err("the keys aren't sorted".to_string());
+        }
+        for key in &self.public_keys {
+            RsaPublicKey::from_public_key_pem(key)
+                .map_err(|err| format!("a key is not PEM-encoded: {}", err))?;
+        }
+
+        Ok(())
+    }
+
+    pub fn required(&self) -> usize {
+        self.required
+    }
+
+    pub fn public_keys(&self) -> Vec<RsaPublicKey> {
+        self.public_keys
+            .iter()
+            .map(|key| RsaPublicKey::from_public_key_pem(key).unwrap())
+            .collect()
+    }
+
+    pub fn locking_script(&self) -> Script {
+        Script::multisig(self.required, &self.public_keys())
+    }
+
+    /// the co-signers can compare it to make sure they got the same keys
+    pub fn address(&self) -> String {
+        self.locking_script().address()
+    }
+
+    pub fn compute_balance(&self, chain: &Blockchain) -> u32 {
+        let locking_script = self.locking_script();
+
+        chain
+            .utxos
+            .iter()
+            .filter(|(_, _, tx_out)| tx_out.locking_script == locking_script)
+            .map(|(_, _, tx_out)| tx_out.amount)
+            .sum()
+    }
+
+    /// the balance without the coinbase outputs which aren't mature yet
+    pub fn compute_spendable_balance(&self, chain: &Blockchain) -> u32 {
+        let locking_script = self.locking_script();
+        let branch = chain.main_branch();
+
+        chain
+            .utxos
+            .iter()
+            .filter(|(hash, _, tx_out)| {
+                tx_out.locking_script == locking_script && branch.is_spendable(hash, &chain.params)
+            })
+            .map(|(_, _, tx_out)| tx_out.amount)
+            .sum()
+    }
+}
+
+/// a transaction spending the coins of a multisig address,
+/// passed from co-signer to co-signer until enough of them signed it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MultisigSpend {
+    pub address: MultisigAddress,
+    /// without the unlocking scripts
+    pub transaction: Transaction,
+    /// the transactions with the spent outputs, in the order of the inputs,
+    /// their hashes are checked against the inputs, so the co-signers can trust the amounts they spend
+    pub previous_transactions: Vec<Transaction>,
+    // in the order of the address' keys
+    signatures: Vec<Option<Vec<u8>>>,
+}
+
+impl MultisigSpend {
+    /// the change goes back to the address
+    pub fn new(
+        address: MultisigAddress,
+        amount: u32,
+        transaction_fee: u32,
+        payee: Script,
+        chain: &Blockchain,
+    ) -> Result<Self, String> {
+        let transaction = Transaction::new_unsigned(
+            amount,
+            transaction_fee,
+            &address.locking_script(),
+            payee,
+            0,
+            None,
+            chain,
+        )?;
+        let previous_transactions = transaction
+            .transaction_inputs
+            .iter()
+            .enumerate()
+            .map(|(index, tx_in)| {
+                tx_in.get_used_transaction(chain).ok_or(format!(
+                    "Input {} spends an output which isn't in the chain",
+                    index
+                ))
+            })
+            .collect::<Result<_, _>>()?;
+        let signatures = vec![None; address.public_keys.len()];
+
+        Ok(Self {
+            address,
+            transaction,
+            previous_transactions,
+            signatures,
+        })
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let spend = read_to_string(path)
+            .map_err(|err| format!("Failed to read the multisig spend from {:?}: {}", path, err))?;
+
+        let spend: Self = serde_json::from_str(&spend)
+            .map_err(|err| format!("{:?} is no valid multisig spend: {}", path, err))?;
+        spend
+            .address
+            .check()
+            .map_err(|err| format!("{:?} is no valid multisig spend: {}", path, err))?;
+        if spend.signatures.len() != spend.address.public_keys.len() {
+            return Err(format!(
+                "{:?} is no valid multisig spend: it doesn't have a place for every key's signature",
+                path
+            ));
+        }
+        spend
+            .check_previous_transactions()
+            .map_err(|err| format!("{:?} is no valid multisig spend: {}", path, err))?;
+
+        Ok(spend)
+    }
+
+    fn check_previous_transactions(&self) -> Result<(), String> {
+        if self.previous_transactions.len() != self.transaction.transaction_inputs.len() {
+            return Err("it doesn't have the previous transaction of every input".to_string());
+        }
+
+        for (index, (tx_in, previous_transaction)) in self
+            .transaction
+            .transaction_inputs
+            .iter()
+            .zip(&self.previous_transactions)
+            .enumerate()
+        {
+            if previous_transaction.hash() != tx_in.prev_transaction_hash
+                || previous_transaction.transaction_outputs.len()
+                    <= tx_in.prev_transaction_index as usize
+            {
+                return Err(format!(
+                    "input {} doesn't spend an output of its previous transaction",
+                    index
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// the outputs the inputs spend, in their order
+    pub fn spent_outputs(&self) -> Vec<&TransactionOutput> {
+        self.transaction
+            .transaction_inputs
+            .iter()
+            .zip(&self.previous_transactions)
+            .map(|(tx_in, previous_transaction)| {
+                &previous_transaction.transaction_outputs[tx_in.prev_transaction_index as usize]
+            })
+            .collect()
+    }
+
+    /// what the outputs don't get of the inputs, None if they get more or the amounts overflow
+    pub fn fee(&self) -> Option<u32> {
+        let inputs = checked_sum(
+            self.spent_outputs()
+                .iter()
+                .map(|spent_output| spent_output.amount),
+        )?;
+
+        inputs.checked_sub(self.transaction.tx_outs_sum()?)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), String> {
+        write(path, serde_json::to_string_pretty(self).unwrap())
+            .map_err(|err| format!("Failed to write the multisig spend to {:?}: {}", path, err))
+    }
+
+    /// add the signature of one of the co-signers, replacing an earlier one of them
+    pub fn sign(&mut self, sign_key: &RsaPrivateKey) -> Result<(), String> {
+        let public_key = sign_key.to_public_key();
+        let index = self
+            .address
+            .public_keys()
+            .iter()
+            .position(|key| *key == public_key)
+            .ok_or("The key isn't one of the multisig address' keys")?;
+
+        self.signatures[index] = Some(sign(&self.transaction.signature_hash(), sign_key));
+
+        Ok(())
+    }
+
+    /// the signatures which are valid for the transaction, in the order of the keys
+    fn valid_signatures(&self) -> Vec<Vec<u8>> {
+        let signature_hash = self.transaction.signature_hash();
+
+        self.signatures
+            .iter()
+            .zip(self.address.public_keys())
+            .filter_map(|(signature, key)| {
+                let signature = signature.as_ref()?;
+                let key = key.to_public_key_der().unwrap();
+
+                verify_signature(&signature_hash, signature, key.as_ref())
+                    .then(|| signature.clone())
+            })
+            .collect()
+    }
+
+    pub fn signature_count(&self) -> usize {
+        self.valid_signatures().len()
+    }
+
+    /// the transaction, unlocked with the required number of signatures
+    pub fn finalize(&self) -> Result<Transaction, String> {
+        let signatures = self.valid_signatures();
+        if signatures.len() < self.address.required {
+            return Err(format!(
+                "Only {} of the {} required signatures are there",
+                signatures.len(),
+                self.address.required
+            ));
+        }
+
+        let unlocking_script = Script::new(
+            signatures
+                .into_iter()
+                .take(self.address.required)
+                .map(Op::Push)
+                .collect(),
+        );
+
+        let mut transaction = self.transaction.clone();
+        for tx_in in &mut transaction.transaction_inputs {
+            tx_in.unlocking_script = unlocking_script.clone();
+        }
+
+        Ok(transaction)
+    }
+}
+
+/// a PEM-encoded public key or a multisig address
+pub fn payee_from_file(path: &Path) -> Result<Script, String> {
+    let payee = read_to_string(path)
+        .map_err(|err| format!("Failed to read the payee from {:?}: {}", path, err))?;
+
+    match RsaPublicKey::from_public_key_pem(&payee) {
+        Ok(public_key) => Ok(Script::pay_to_public_key(&public_key)),
+        Err(_) => Ok(MultisigAddress::from_file(path)
+            .map_err(|_| {
+                format!(
+                    "{:?} is neither a PEM-encoded public key file nor a multisig address. Most probably you provided a private key file instead",
+                    path
+                )
+            })?
+            .locking_script()),
+    }
+}
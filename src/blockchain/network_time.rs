@@ -0,0 +1,102 @@
+use std::collections::HashMap;
+
+use crate::{
+    consts::{MAX_CLOCK_OFFSET, MIN_TIME_SAMPLES},
+    util::time_since_unix_epoch,
+};
+
+/// our estimate of the network's time from the clocks of our peers
+#[derive(Debug, Clone, Default)]
+pub struct NetworkTime {
+    // how many milliseconds the clock of a peer is ahead of ours
+    offsets: HashMap<String, i128>,
+}
+
+impl NetworkTime {
+    /// sample the clock of the peer from the time it sent a message, returns its offset
+    pub fn add_sample(&mut self, peer: &str, peer_time: u128) -> i128 {
+        let offset = peer_time as i128 - time_since_unix_epoch() as i128;
+        self.offsets.insert(peer.to_string(), offset);
+
+        offset
+    }
+
+    pub fn remove_peer(&mut self, peer: &str) {
+        self.offsets.remove(peer);
+    }
+
+    /// the median of the offsets of the peers and ours,
+    /// 0 if there are too few peers or it's too big to trust it
+    pub fn offset(&self) -> i128 {
+        if self.offsets.len() < MIN_TIME_SAMPLES {
+            return 0;
+        }
+
+        let mut offsets: Vec<_> = self.offsets.values().copied().chain([0]).collect();
+        offsets.sort_unstable();
+
+        let middle = offsets.len() / 2;
+        let offset = if offsets.len() % 2 == 0 {
+            (offsets[middle - 1] + offsets[middle]) / 2
+        } else {
+            offsets[middle]
+        };
+        if offset.unsigned_abs() > MAX_CLOCK_OFFSET.as_millis() {
+            0
+        } else {
+            offset
+        }
+    }
+
+    /// our time adjusted by the offset
+    pub fn now(&self) -> u128 {
+        (time_since_unix_epoch() as i128 + self.offset()) as u128
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn network_time(offsets: &[i128]) -> NetworkTime {
+        NetworkTime {
+            offsets: offsets
+                .iter()
+                .enumerate()
+                .map(|(peer, offset)| (peer.to_string(), *offset))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn too_few_samples_are_ignored() {
+        assert_eq!(network_time(&[1000; MIN_TIME_SAMPLES - 1]).offset(), 0);
+        assert_eq!(network_time(&[1000; MIN_TIME_SAMPLES]).offset(), 1000);
+    }
+
+    #[test]
+    fn the_offset_is_the_median_including_ours() {
+        // with ours: 0, 10, 20, 30, 40, 50
+        assert_eq!(network_time(&[10, 20, 30, 40, 50]).offset(), 25);
+        // with ours: -30, 0, 10, 20, 30, 40, 50
+        assert_eq!(network_time(&[-30, 10, 20, 30, 40, 50]).offset(), 20);
+    }
+
+    #[test]
+    fn too_big_offsets_are_not_trusted() {
+        let max = MAX_CLOCK_OFFSET.as_millis() as i128;
+
+        assert_eq!(network_time(&[max; MIN_TIME_SAMPLES]).offset(), max);
+        assert_eq!(network_time(&[-max; MIN_TIME_SAMPLES]).offset(), -max);
+        assert_eq!(network_time(&[max + 1; MIN_TIME_SAMPLES]).offset(), 0);
+        assert_eq!(network_time(&[-max - 1; MIN_TIME_SAMPLES]).offset(), 0);
+    }
+
+    #[test]
+    fn removed_peers_are_no_samples() {
+        let mut time = network_time(&[1000; MIN_TIME_SAMPLES]);
+        time.remove_peer("0");
+
+        assert_eq!(time.offset(), 0);
+    }
+}
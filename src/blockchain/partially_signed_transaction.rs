@@ -0,0 +1,226 @@
+use std::{
+    fs::{read_to_string, write},
+    path::Path,
+};
+
+use rsa::{RsaPrivateKey, RsaPublicKey};
+use serde::{Deserialize, Serialize};
+
+use crate::util::checked_sum;
+
+use super::{
+    interpreter::{verify_script, ScriptContext},
+    script::sign,
+    Blockchain, Op, RelativeLock, Script, Transaction, TransactionOutput,
+};
+
+/// a transaction created without the private key (by a watch-only node),
+/// to be signed on another machine and sent by the first one again
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PartiallySignedTransaction {
+    /// without the unlocking scripts
+    pub transaction: Transaction,
+    /// in the order of the transaction's inputs
+    pub inputs: Vec<PartiallySignedInput>,
+}
+
+/// what the signer needs to know about an input, it doesn't have the chain
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PartiallySignedInput {
+    /// the transaction with the spent output, its hash is checked against the input,
+    /// so the signer can trust the amount it spends
+    pub previous_transaction: Transaction,
+    pub signature: Option<Vec<u8>>,
+}
+
+impl PartiallySignedTransaction {
+    /// like Transaction::new, but only with the payer's public key
+    pub fn new(
+        amount: u32,
+        transaction_fee: u32,
+        payer: &RsaPublicKey,
+        payee: Script,
+        lock_time: u64,
+        relative_lock: Option<RelativeLock>,
+        chain: &Blockchain,
+    ) -> Result<Self, String> {
+        let transaction = Transaction::new_unsigned(
+            amount,
+            transaction_fee,
+            &Script::pay_to_public_key(payer),
+            payee,
+            lock_time,
+            relative_lock,
+            chain,
+        )?;
+        let inputs = transaction
+            .transaction_inputs
+            .iter()
+            .enumerate()
+            .map(|(index, tx_in)| {
+                tx_in
+                    .get_used_transaction(chain)
+                    .map(|previous_transaction| PartiallySignedInput {
+                        previous_transaction,
+                        signature: None,
+                    })
+                    .ok_or(format!(
+                        "Input {} spends an output which isn't in the chain",
+                        index
+                    ))
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            transaction,
+            inputs,
+        })
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let transaction = read_to_string(path).map_err(|err| {
+            format!(
+                "Failed to read the partially signed transaction from {:?}: {}",
+                path, err
+            )
+        })?;
+
+        let transaction: Self = serde_json::from_str(&transaction).map_err(|err| {
+            format!(
+                "{:?} is no valid partially signed transaction: {}",
+                path, err
+            )
+        })?;
+        transaction.check().map_err(|err| {
+            format!(
+                "{:?} is no valid partially signed transaction: {}",
+                path, err
+            )
+        })?;
+
+        Ok(transaction)
+    }
+
+    fn check(&self) -> Result<(), String> {
+        if self.inputs.len() != self.transaction.transaction_inputs.len() {
+            return Err("it doesn't describe every input".to_string());
+        }
+
+        for (index, (tx_in, input)) in self
+            .transaction
+            .transaction_inputs
+            .iter()
+            .zip(&self.inputs)
+            .enumerate()
+        {
+            let previous_transaction = &input.previous_transaction;
+            if previous_transaction.hash() != tx_in.prev_transaction_hash
+                || previous_transaction.transaction_outputs.len()
+                    <= tx_in.prev_transaction_index as usize
+            {
+                return Err(format!(
+                    "input {} doesn't spend an output of its previous transaction",
+                    index
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), String> {
+        write(path, serde_json::to_string_pretty(self).unwrap()).map_err(|err| {
+            format!(
+                "Failed to write the partially signed transaction to {:?}: {}",
+                path, err
+            )
+        })
+    }
+
+    /// the outputs the inputs spend, in their order
+    pub fn spent_outputs(&self) -> Vec<&TransactionOutput> {
+        self.transaction
+            .transaction_inputs
+            .iter()
+            .zip(&self.inputs)
+            .map(|(tx_in, input)| {
+                &input.previous_transaction.transaction_outputs
+                    [tx_in.prev_transaction_index as usize]
+            })
+            .collect()
+    }
+
+    /// sign the inputs spending outputs paid to the key, returns how many
+    pub fn sign(&mut self, sign_key: &RsaPrivateKey) -> usize {
+        let public_key = sign_key.to_public_key();
+        let signature = sign(&self.transaction.signature_hash(), sign_key);
+
+        let paid_to_key: Vec<_> = self
+            .spent_outputs()
+            .iter()
+            .map(|spent_output| spent_output.is_paid_to(&public_key))
+            .collect();
+
+        let mut signed = 0;
+        for (input, paid_to_key) in self.inputs.iter_mut().zip(paid_to_key) {
+            if paid_to_key {
+                input.signature = Some(signature.clone());
+                signed += 1;
+            }
+        }
+
+        signed
+    }
+
+    pub fn signed_inputs(&self) -> usize {
+        self.inputs
+            .iter()
+            .filter(|input| input.signature.is_some())
+            .count()
+    }
+
+    /// what the outputs don't get of the inputs, None if they get more or the amounts overflow
+    pub fn fee(&self) -> Option<u32> {
+        let inputs = checked_sum(
+            self.spent_outputs()
+                .iter()
+                .map(|spent_output| spent_output.amount),
+        )?;
+
+        inputs.checked_sub(self.transaction.tx_outs_sum()?)
+    }
+
+    /// the transaction with the signatures in the unlocking scripts,
+    /// the scripts are run to make sure it can be sent
+    pub fn finalize(&self) -> Result<Transaction, String> {
+        let mut transaction = self.transaction.clone();
+
+        for (index, input) in self.inputs.iter().enumerate() {
+            let signature = input
+                .signature
+                .as_ref()
+                .ok_or(format!("Input {} isn't signed yet", index))?;
+
+            transaction.transaction_inputs[index].unlocking_script =
+                Script::new(vec![Op::Push(signature.clone())]);
+        }
+
+        let signature_hash = transaction.signature_hash();
+        for (index, spent_output) in self.spent_outputs().into_iter().enumerate() {
+            let context = ScriptContext {
+                transaction: &transaction,
+                index,
+                signature_hash: &signature_hash,
+            };
+
+            verify_script(
+                &transaction.transaction_inputs[index].unlocking_script,
+                &spent_output.locking_script,
+                &context,
+            )
+            .map_err(|err| format!("Input {}: {}", index, err))?;
+        }
+
+        Ok(transaction)
+    }
+}
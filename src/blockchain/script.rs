@@ -0,0 +1,185 @@
+use rsa::{
+    pkcs8::{FromPublicKey, ToPublicKey},
+    Hash, PaddingScheme, PublicKey, RsaPrivateKey, RsaPublicKey,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::util::{sha256, to_hex};
+
+use super::interpreter::MAX_MULTISIG_KEYS;
+
+/// the size of the preimages HTLCs lock their coins with, in bytes
+pub const HTLC_PREIMAGE_SIZE: usize = 32;
+
+/// an instruction of a script, see the interpreter for what they do
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub enum Op {
+    Push(Vec<u8>),
+    Dup,
+    Drop,
+    Swap,
+    /// pushes the size of the top element, which stays on the stack
+    Size,
+    Sha256,
+    Equal,
+    EqualVerify,
+    Verify,
+    If,
+    NotIf,
+    Else,
+    EndIf,
+    CheckSig,
+    CheckSigVerify,
+    CheckMultiSig,
+    CheckMultiSigVerify,
+    CheckLockTimeVerify,
+    CheckSequenceVerify,
+}
+
+impl Op {
+    pub fn number(number: u64) -> Self {
+        Op::Push(encode_number(number))
+    }
+
+    pub fn public_key(public_key: &RsaPublicKey) -> Self {
+        Op::Push(public_key.to_public_key_der().unwrap().as_ref().to_vec())
+    }
+}
+
+/// outputs are locked by a locking script, inputs unlock them with an unlocking script
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+pub struct Script {
+    pub ops: Vec<Op>,
+}
+
+impl Script {
+    pub fn new(ops: Vec<Op>) -> Self {
+        Self { ops }
+    }
+
+    /// the output can be spent with a signature of the key
+    pub fn pay_to_public_key(public_key: &RsaPublicKey) -> Self {
+        Self::new(vec![Op::public_key(public_key), Op::CheckSig])
+    }
+
+    /// the output can be spent with signatures of required of the keys,
+    /// the unlocking script pushes them in the order of the keys
+    pub fn multisig(required: usize, public_keys: &[RsaPublicKey]) -> Self {
+        let mut ops = vec![Op::number(required as u64)];
+        ops.extend(public_keys.iter().map(Op::public_key));
+        ops.push(Op::number(public_keys.len() as u64));
+        ops.push(Op::CheckMultiSig);
+
+        Self::new(ops)
+    }
+
+    /// the recipient can spend the output with a signature and the preimage of the hash (and true),
+    /// the sender with a signature (and false) once the timeout passed
+    ///
+    /// the preimage has to be HTLC_PREIMAGE_SIZE bytes, so one which is too big for the chain on
+    /// the other side of a swap can't take the coins here
+    pub fn htlc(
+        hash: &[u8],
+        recipient: &RsaPublicKey,
+        sender: &RsaPublicKey,
+        timeout: u64,
+    ) -> Self {
+        Self::new(vec![
+            Op::If,
+            Op::Size,
+            Op::number(HTLC_PREIMAGE_SIZE as u64),
+            Op::EqualVerify,
+            Op::Sha256,
+            Op::Push(hash.to_vec()),
+            Op::EqualVerify,
+            Op::public_key(recipient),
+            Op::Else,
+            Op::number(timeout),
+            Op::CheckLockTimeVerify,
+            Op::public_key(sender),
+            Op::EndIf,
+            Op::CheckSig,
+        ])
+    }
+
+    /// the hex-encoded hash of a locking script, to show who an output pays to
+    pub fn address(&self) -> String {
+        to_hex(&sha256(&bincode::serialize(self).unwrap()))
+    }
+
+    /// unlocking scripts may only push data
+    pub fn is_push_only(&self) -> bool {
+        self.ops.iter().all(|op| matches!(op, Op::Push(_)))
+    }
+
+    /// the serialized size (bincode encoded)
+    pub fn size(&self) -> usize {
+        bincode::serialized_size(self).unwrap() as usize
+    }
+
+    /// the number of signatures to check, the maximum for a multisig without a number of keys before it
+    pub fn sigops(&self) -> usize {
+        self.ops
+            .iter()
+            .enumerate()
+            .map(|(i, op)| match op {
+                Op::CheckSig | Op::CheckSigVerify => 1,
+                Op::CheckMultiSig | Op::CheckMultiSigVerify => {
+                    match i.checked_sub(1).map(|prev| &self.ops[prev]) {
+                        Some(Op::Push(number)) => decode_number(number)
+                            .map(|keys| (keys as usize).min(MAX_MULTISIG_KEYS))
+                            .unwrap_or(MAX_MULTISIG_KEYS),
+                        _ => MAX_MULTISIG_KEYS,
+                    }
+                }
+                _ => 0,
+            })
+            .sum()
+    }
+}
+
+/// numbers are little endian without trailing zero bytes, 0 is empty
+pub fn encode_number(number: u64) -> Vec<u8> {
+    let mut bytes = number.to_le_bytes().to_vec();
+    while bytes.last() == Some(&0) {
+        bytes.pop();
+    }
+
+    bytes
+}
+
+/// a number pushed by Op::number, None if it has more than 8 bytes
+pub fn decode_number(bytes: &[u8]) -> Option<u64> {
+    if bytes.len() > 8 {
+        return None;
+    }
+
+    let mut le_bytes = [0; 8];
+    le_bytes[..bytes.len()].copy_from_slice(bytes);
+
+    Some(u64::from_le_bytes(le_bytes))
+}
+
+/// signature_hash is the one of the whole transaction
+pub fn sign(signature_hash: &[u8], sign_key: &RsaPrivateKey) -> Vec<u8> {
+    sign_key
+        .sign(
+            PaddingScheme::new_pkcs1v15_sign(Some(Hash::SHA2_256)),
+            signature_hash,
+        )
+        .unwrap()
+}
+
+/// public_key is DER encoded, a key which can't be decoded doesn't verify anything
+pub fn verify_signature(signature_hash: &[u8], signature: &[u8], public_key: &[u8]) -> bool {
+    match RsaPublicKey::from_public_key_der(public_key) {
+        Ok(public_key) => public_key
+            .verify(
+                PaddingScheme::new_pkcs1v15_sign(Some(Hash::SHA2_256)),
+                signature_hash,
+                signature,
+            )
+            .is_ok(),
+        Err(_) => false,
+    }
+}
@@ -0,0 +1,113 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    sync::Mutex,
+};
+
+use rayon::prelude::*;
+
+use crate::{consts::MAX_SIGNATURE_CACHE_SIZE, util::sha256};
+
+use super::{
+    interpreter::{verify_script, ScriptContext},
+    ScriptError, Transaction, TransactionError, TransactionOutput,
+};
+
+/// the inputs whose scripts were verified already,
+/// so transactions from the mempool aren't verified again when their block arrives
+#[derive(Debug, Default)]
+pub struct SignatureCache {
+    verified: Mutex<VerifiedInputs>,
+}
+
+#[derive(Debug, Default)]
+struct VerifiedInputs {
+    hashes: HashSet<Vec<u8>>,
+    // the oldest first, they are dropped when the cache is full
+    order: VecDeque<Vec<u8>>,
+}
+
+impl SignatureCache {
+    /// verify the scripts of the transactions' inputs on all cores, skipping the ones verified before,
+    /// every transaction comes with the outputs its inputs spend
+    ///
+    /// on failure, returns the position of the failing transaction in the iterator
+    pub fn verify<'a>(
+        &self,
+        transactions: impl Iterator<Item = (&'a Transaction, Vec<TransactionOutput>)>,
+    ) -> Result<(), (usize, TransactionError)> {
+        let tx_ins: Vec<_> = transactions
+            .enumerate()
+            .flat_map(|(position, (transaction, spent_outputs))| {
+                let signature_hash = transaction.signature_hash();
+
+                spent_outputs
+                    .into_iter()
+                    .enumerate()
+                    .map(move |(index, tx_out)| {
+                        (position, transaction, index, tx_out, signature_hash.clone())
+                    })
+            })
+            .collect();
+
+        tx_ins
+            .par_iter()
+            .try_for_each(|(position, transaction, index, tx_out, signature_hash)| {
+                let context = ScriptContext {
+                    transaction,
+                    index: *index,
+                    signature_hash,
+                };
+
+                self.verify_one(&context, tx_out).map_err(|error| {
+                    (
+                        *position,
+                        TransactionError::Script {
+                            index: *index,
+                            error,
+                        },
+                    )
+                })
+            })
+    }
+
+    fn verify_one(
+        &self,
+        context: &ScriptContext,
+        tx_out: &TransactionOutput,
+    ) -> Result<(), ScriptError> {
+        let tx_in = &context.transaction.transaction_inputs[context.index];
+        // the hash covers the unlocking script, the signed data and the locking script
+        let hash = sha256(
+            &[
+                bincode::serialize(tx_in).unwrap(),
+                context.signature_hash.to_vec(),
+                bincode::serialize(&tx_out.locking_script).unwrap(),
+            ]
+            .concat(),
+        );
+
+        if self.verified.lock().unwrap().hashes.contains(&hash) {
+            return Ok(());
+        }
+
+        verify_script(&tx_in.unlocking_script, &tx_out.locking_script, context)?;
+        self.verified.lock().unwrap().insert(hash);
+
+        Ok(())
+    }
+}
+
+impl VerifiedInputs {
+    fn insert(&mut self, hash: Vec<u8>) {
+        if !self.hashes.insert(hash.clone()) {
+            return;
+        }
+        self.order.push_back(hash);
+
+        if self.order.len() > MAX_SIGNATURE_CACHE_SIZE {
+            if let Some(oldest) = self.order.pop_front() {
+                self.hashes.remove(&oldest);
+            }
+        }
+    }
+}
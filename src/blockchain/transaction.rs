@@ -1,135 +1,461 @@
-use rsa::RsaPublicKey;
+use std::{cmp::Reverse, collections::HashSet, mem};
+
+use rsa::RsaPrivateKey;
 use serde::{Deserialize, Serialize};
 
-use crate::util::sha256;
+use crate::{
+    consts::{MAX_STANDARD_TRANSACTION_SIGOPS, MAX_STANDARD_TRANSACTION_SIZE},
+    util::{checked_sum, sha256},
+};
+
+use super::{
+    lock_time::{
+        LOCK_TIME_THRESHOLD, RELATIVE_LOCK_TIME_VERSION, SEQUENCE_FINAL, SEQUENCE_LOCK_TIME_ONLY,
+    },
+    script::sign,
+    Blockchain, Branch, ChainParams, Op, RelativeLock, Script, TransactionError, TransactionInput,
+    TransactionOutput, Wallet,
+};
 
-use super::{Blockchain, TransactionInput, TransactionOutput, Wallet};
+/// the most data a coinbase may carry after the height of its block:
+/// the extra nonce, or the message of a genesis block
+pub const MAX_COINBASE_DATA_SIZE: usize = 100;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Transaction {
+    pub version: u32,
     pub transaction_inputs: Vec<TransactionInput>,
     pub transaction_outputs: Vec<TransactionOutput>,
-}
-
-// extra struct only to check the signature (we can't check with the signature IN the struct)
-#[derive(Serialize)]
-struct TransactionForCheck {
-    pub transaction_inputs: Vec<TransactionInput>,
-    pub transaction_outputs: Vec<TransactionOutput>,
+    /// the transaction can only be mined after this block height or unix timestamp (in seconds),
+    /// see LOCK_TIME_THRESHOLD
+    pub lock_time: u64,
 }
 
 impl Transaction {
+    /// the inputs of a transaction with a relative lock all have it
     pub fn new(
         amount: u32,
         transaction_fee: u32,
         wallet: Option<Wallet>,
-        payee: RsaPublicKey,
+        payee: Script,
+        lock_time: u64,
+        relative_lock: Option<RelativeLock>,
         chain: &mut Blockchain,
+    ) -> Result<Self, String> {
+        match wallet {
+            Some(keypair) => {
+                let mut transaction = Self::new_unsigned(
+                    amount,
+                    transaction_fee,
+                    &Script::pay_to_public_key(&keypair.public_key),
+                    payee,
+                    lock_time,
+                    relative_lock,
+                    chain,
+                )?;
+                transaction.sign(&keypair.private_key);
+
+                transaction
+                    .check_standard(&chain.params)
+                    .map_err(|err| err.to_string())?;
+
+                Ok(transaction)
+            }
+            None => Ok(Self::new_coinbase(
+                chain.next_height(),
+                vec![TransactionOutput::with_script(amount, payee)],
+            )),
+        }
+    }
+
+    /// spend outputs locked by the payer's locking script, the change goes back to it,
+    /// the inputs still have to be unlocked
+    pub fn new_unsigned(
+        amount: u32,
+        transaction_fee: u32,
+        payer: &Script,
+        payee: Script,
+        lock_time: u64,
+        relative_lock: Option<RelativeLock>,
+        chain: &Blockchain,
     ) -> Result<Self, String> {
         let total_to_pay = amount + transaction_fee;
 
         let mut transaction = Self {
+            version: RELATIVE_LOCK_TIME_VERSION,
             transaction_inputs: vec![],
             transaction_outputs: vec![],
+            lock_time,
         };
 
-        if let Some(keypair) = wallet {
-            let mut utxos: Vec<_> = chain
-                .utxos
-                .iter()
-                .filter(|(_, _, tx_out)| tx_out.payee == keypair.public_key)
-                .collect();
+        // the lock time only counts if an input isn't final
+        let sequence = match relative_lock {
+            Some(relative_lock) => relative_lock.to_sequence(),
+            None if lock_time != 0 => SEQUENCE_LOCK_TIME_ONLY,
+            None => SEQUENCE_FINAL,
+        };
 
-            utxos.sort_by_key(|(_, _, tx_out)| tx_out.amount);
+        let branch = chain.main_branch();
 
-            let mut utxos_iter = utxos.iter();
-            let mut total_amount = 0;
+        let mut utxos: Vec<_> = chain
+            .utxos
+            .iter()
+            .filter(|(hash, _, tx_out)| {
+                tx_out.locking_script == *payer && branch.is_spendable(hash, &chain.params)
+            })
+            .collect();
 
-            loop {
-                let (hash, index, utxo) = match utxos_iter.next() {
-                    Some(value) => value,
-                    None => break,
-                };
+        // the biggest first, to need as few inputs as possible
+        utxos.sort_by_key(|(_, _, tx_out)| Reverse(tx_out.amount));
 
-                total_amount += utxo.amount;
+        let mut total_amount = 0;
 
-                transaction.transaction_inputs.push(TransactionInput::new(
-                    hash.clone(),
-                    *index as u32,
-                    Some(utxo.payee.clone()),
-                    &keypair.private_key,
-                ));
+        for (hash, index, utxo) in utxos {
+            if total_amount >= total_to_pay {
+                break;
             }
 
-            if total_amount < total_to_pay {
-                return Err("You do not have enough money in this wallet".to_string());
-            }
+            total_amount += utxo.amount;
+
+            let mut tx_in = TransactionInput::new(hash.clone(), *index);
+            tx_in.sequence = sequence;
+            transaction.transaction_inputs.push(tx_in);
+        }
+
+        if total_amount < total_to_pay {
+            return Err("You do not have enough money in this wallet".to_string());
+        }
+
+        transaction
+            .transaction_outputs
+            .push(TransactionOutput::with_script(amount, payee));
 
+        // change transaction output
+        if total_amount > total_to_pay {
             transaction
                 .transaction_outputs
-                .push(TransactionOutput::new(amount, payee));
-
-            // change transaction output
-            if total_amount > total_to_pay {
-                transaction.transaction_outputs.push(TransactionOutput::new(
+                .push(TransactionOutput::with_script(
                     total_amount - total_to_pay,
-                    keypair.public_key.clone(),
+                    payer.clone(),
                 ));
+        }
+
+        Ok(transaction)
+    }
+
+    /// a transaction creating new money in the block at the height, its only input doesn't spend anything,
+    /// the height in its unlocking script tells it apart from the coinbases of the other blocks
+    pub fn new_coinbase(height: u64, transaction_outputs: Vec<TransactionOutput>) -> Self {
+        let mut tx_in = TransactionInput::new(vec![], 0);
+        tx_in.unlocking_script = Script::new(vec![Op::Push(height.to_be_bytes().to_vec())]);
+
+        Self {
+            version: RELATIVE_LOCK_TIME_VERSION,
+            transaction_inputs: vec![tx_in],
+            transaction_outputs,
+            lock_time: 0,
+        }
+    }
+
+    /// unlock all inputs spending outputs paid to the key with a signature
+    pub fn sign(&mut self, sign_key: &RsaPrivateKey) {
+        let signature = sign(&self.signature_hash(), sign_key);
+
+        for tx_in in &mut self.transaction_inputs {
+            tx_in.unlocking_script = Script::new(vec![Op::Push(signature.clone())]);
+        }
+    }
+
+    /// what the inputs sign: the whole transaction without the unlocking scripts
+    pub fn signature_hash(&self) -> Vec<u8> {
+        let mut transaction = self.clone();
+        for tx_in in &mut transaction.transaction_inputs {
+            tx_in.unlocking_script = Script::default();
+        }
+
+        sha256(&bincode::serialize(&transaction).unwrap())
+    }
+
+    /// a coinbase has a single input which spends nothing,
+    /// its unlocking script only carries the height of its block and some data (its hash covers both)
+    pub fn is_coinbase(&self) -> bool {
+        match self.transaction_inputs.as_slice() {
+            [tx_in] => {
+                tx_in.prev_transaction_hash.is_empty()
+                    && tx_in.prev_transaction_index == 0
+                    && match tx_in.unlocking_script.ops.as_slice() {
+                        [Op::Push(height)] => height.len() == mem::size_of::<u64>(),
+                        [Op::Push(height), Op::Push(data)] => {
+                            height.len() == mem::size_of::<u64>()
+                                && data.len() <= MAX_COINBASE_DATA_SIZE
+                        }
+                        _ => false,
+                    }
             }
-        } else {
-            transaction.transaction_inputs.push(TransactionInput {
-                prev_transaction_hash: vec![],
-                prev_transaction_index: 0,
-                signature: vec![],
-                payer: None,
+            _ => false,
+        }
+    }
+
+    /// the height of the block a coinbase is in, None if it's no coinbase
+    pub fn coinbase_height(&self) -> Option<u64> {
+        if !self.is_coinbase() {
+            return None;
+        }
+
+        match self.transaction_inputs[0].unlocking_script.ops.first() {
+            Some(Op::Push(height)) => Some(u64::from_be_bytes(height.as_slice().try_into().ok()?)),
+            _ => None,
+        }
+    }
+
+    /// the number of signatures its scripts check,
+    /// the locking scripts of the outputs count instead of the ones of the outputs it spends
+    pub fn sigops(&self) -> usize {
+        let unlocking: usize = self
+            .transaction_inputs
+            .iter()
+            .map(|tx_in| tx_in.unlocking_script.sigops())
+            .sum();
+        let locking: usize = self
+            .transaction_outputs
+            .iter()
+            .map(|tx_out| tx_out.locking_script.sigops())
+            .sum();
+
+        unlocking + locking
+    }
+
+    /// the serialized size (bincode encoded)
+    pub fn size(&self) -> usize {
+        bincode::serialized_size(self).unwrap() as usize
+    }
+
+    /// the consensus limits every transaction in a block has to stay within
+    pub fn check_limits(&self, params: &ChainParams) -> Result<(), TransactionError> {
+        if self.size() > params.max_transaction_size {
+            return Err(TransactionError::TooBig {
+                max: params.max_transaction_size,
+            });
+        }
+        if self.transaction_inputs.len() > params.max_transaction_inputs {
+            return Err(TransactionError::TooManyInputs {
+                max: params.max_transaction_inputs,
             });
+        }
+        if self.transaction_outputs.len() > params.max_transaction_outputs {
+            return Err(TransactionError::TooManyOutputs {
+                max: params.max_transaction_outputs,
+            });
+        }
 
-            transaction
-                .transaction_outputs
-                .push(TransactionOutput::new(amount, payee));
+        Ok(())
+    }
+
+    /// the stricter policy for transactions we relay and mine
+    pub fn check_standard(&self, params: &ChainParams) -> Result<(), TransactionError> {
+        self.check_limits(params)?;
+
+        if self.size() > MAX_STANDARD_TRANSACTION_SIZE {
+            return Err(TransactionError::NonStandard(format!(
+                "the transaction is bigger than the standard {} bytes",
+                MAX_STANDARD_TRANSACTION_SIZE
+            )));
+        }
+        if self.sigops() > MAX_STANDARD_TRANSACTION_SIGOPS {
+            return Err(TransactionError::NonStandard(format!(
+                "the transaction needs more than the standard {} signature checks",
+                MAX_STANDARD_TRANSACTION_SIGOPS
+            )));
+        }
+        if self
+            .transaction_outputs
+            .iter()
+            .any(|tx_out| tx_out.amount == 0)
+        {
+            return Err(TransactionError::NonStandard(
+                "the transaction has an output without any eincoin".to_string(),
+            ));
         }
 
-        Ok(transaction)
+        Ok(())
     }
 
-    pub fn tx_ins_sum(&self, chain: &Blockchain) -> Option<u32> {
-        let mut tx_in_sum = 0;
+    /// pending transactions aren't in the chain yet, but their outputs can be spent as well,
+    /// None if an input is missing or the amounts overflow
+    pub fn tx_ins_sum(&self, branch: &Branch, pending: &[Transaction]) -> Option<u32> {
+        let mut tx_in_sum: u32 = 0;
 
         for tx_in in &self.transaction_inputs {
-            match tx_in.get_used_tx_out(chain) {
-                Some(tx_out) => tx_in_sum += tx_out.amount,
-                None => return None,
-            }
+            let tx_out = tx_in.get_used_tx_out(branch, pending)?;
+            tx_in_sum = tx_in_sum.checked_add(tx_out.amount)?;
         }
 
         Some(tx_in_sum)
     }
 
-    pub fn tx_outs_sum(&self) -> u32 {
-        self.transaction_outputs
+    /// None if the amounts overflow
+    pub fn tx_outs_sum(&self) -> Option<u32> {
+        checked_sum(self.transaction_outputs.iter().map(|tx_out| tx_out.amount))
+    }
+
+    /// whether it can be mined on the branch after the pending transactions
+    pub fn verify(
+        &self,
+        chain: &Blockchain,
+        branch: &Branch,
+        pending: &[Transaction],
+    ) -> Result<(), TransactionError> {
+        self.verify_amounts(branch, pending)?;
+        self.verify_scripts(chain, branch, pending)
+    }
+
+    /// the outputs the inputs spend, pending transactions aren't in the chain yet
+    pub fn spent_outputs(
+        &self,
+        branch: &Branch,
+        pending: &[Transaction],
+    ) -> Result<Vec<TransactionOutput>, TransactionError> {
+        self.transaction_inputs
             .iter()
-            .map(|tx_out| tx_out.amount)
-            .sum()
+            .enumerate()
+            .map(|(index, tx_in)| {
+                tx_in
+                    .get_used_tx_out(branch, pending)
+                    .ok_or(TransactionError::MissingInput { index })
+            })
+            .collect()
+    }
+
+    /// everything but the scripts
+    pub fn verify_amounts(
+        &self,
+        branch: &Branch,
+        pending: &[Transaction],
+    ) -> Result<(), TransactionError> {
+        // only the coinbase may create money without spending anything
+        if self.transaction_inputs.is_empty() {
+            return Err(TransactionError::NoInputs);
+        }
+        if !self.spends_nothing_twice() {
+            return Err(TransactionError::DuplicateInput);
+        }
+
+        let inputs = checked_sum(
+            self.spent_outputs(branch, pending)?
+                .iter()
+                .map(|tx_out| tx_out.amount),
+        )
+        .ok_or(TransactionError::AmountOverflow)?;
+
+        let outputs = self.tx_outs_sum().ok_or(TransactionError::AmountOverflow)?;
+        if outputs > inputs {
+            return Err(TransactionError::Overspend { inputs, outputs });
+        }
+
+        Ok(())
     }
 
-    pub fn verify(&self, chain: &Blockchain) -> bool {
-        let tx_ins_sum = self.tx_ins_sum(chain);
+    /// whether the unlocking scripts of the inputs satisfy the locking scripts of the outputs they spend
+    pub fn verify_scripts(
+        &self,
+        chain: &Blockchain,
+        branch: &Branch,
+        pending: &[Transaction],
+    ) -> Result<(), TransactionError> {
+        let spent_outputs = self.spent_outputs(branch, pending)?;
+
+        chain
+            .signature_cache
+            .verify([(self, spent_outputs)].into_iter())
+            .map_err(|(_, error)| error)
+    }
 
-        if tx_ins_sum.is_none() {
-            return false;
+    /// whether the lock time passed for a block at the height with the median time past,
+    /// it doesn't count if all inputs are final
+    pub fn is_final(&self, height: u64, median_time_past: u128) -> bool {
+        self.lock_time == 0
+            || self
+                .transaction_inputs
+                .iter()
+                .all(|tx_in| tx_in.sequence == SEQUENCE_FINAL)
+            || if self.lock_time < LOCK_TIME_THRESHOLD {
+                self.lock_time < height
+            } else {
+                (self.lock_time as u128 * 1000) < median_time_past
+            }
+    }
+
+    /// whether the lock time and the relative lock times of the inputs passed for the next block on the branch
+    pub fn verify_locks(
+        &self,
+        branch: &Branch,
+        params: &ChainParams,
+    ) -> Result<(), TransactionError> {
+        let height = branch.height();
+        let median_time_past = branch.median_time_past(params);
+
+        if !self.is_final(height, median_time_past) {
+            return Err(TransactionError::Locked {
+                lock_time: self.lock_time,
+            });
         }
 
-        self.tx_outs_sum() <= tx_ins_sum.unwrap()
-            && self.transaction_inputs.iter().all(|tx_in| tx_in.verify())
+        if self.version < RELATIVE_LOCK_TIME_VERSION {
+            return Ok(());
+        }
+
+        for (index, tx_in) in self.transaction_inputs.iter().enumerate() {
+            let relative_lock = match RelativeLock::from_sequence(tx_in.sequence) {
+                Some(relative_lock) => relative_lock,
+                None => continue,
+            };
+
+            // outputs which aren't in the chain yet will be in the same block
+            let (confirmation_height, confirmation_time) = branch
+                .confirmation(&tx_in.prev_transaction_hash, params)
+                .unwrap_or((height, median_time_past));
+
+            if !relative_lock.is_unlocked(
+                height,
+                median_time_past,
+                confirmation_height,
+                confirmation_time,
+            ) {
+                return Err(TransactionError::RelativeLocked {
+                    index,
+                    relative_lock,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// an output spent by two inputs would be counted twice
+    fn spends_nothing_twice(&self) -> bool {
+        let spent: HashSet<_> = self
+            .transaction_inputs
+            .iter()
+            .map(|tx_in| (&tx_in.prev_transaction_hash, tx_in.prev_transaction_index))
+            .collect();
+
+        spent.len() == self.transaction_inputs.len()
     }
 
+    /// whether the coinbase outputs it spends are mature in the next block on the branch
+    pub fn spends_mature_outputs(&self, branch: &Branch, params: &ChainParams) -> bool {
+        self.transaction_inputs
+            .iter()
+            .all(|tx_in| branch.is_spendable(&tx_in.prev_transaction_hash, params))
+    }
+
+    /// the signature hash, so changing the unlocking scripts of an unmined transaction doesn't change it,
+    /// only a coinbase's covers its unlocking script (the extra nonce)
     pub fn hash(&self) -> Vec<u8> {
-        sha256(
-            &bincode::serialize(&TransactionForCheck {
-                transaction_inputs: self.transaction_inputs.clone(),
-                transaction_outputs: self.transaction_outputs.clone(),
-            })
-            .unwrap(),
-        )
+        if self.is_coinbase() {
+            sha256(&bincode::serialize(self).unwrap())
+        } else {
+            self.signature_hash()
+        }
     }
 }
@@ -1,102 +1,57 @@
-use rsa::{Hash, PaddingScheme, PublicKey, RsaPrivateKey, RsaPublicKey};
 use serde::{Deserialize, Serialize};
 
-use crate::util::sha256;
-
-use super::{Blockchain, TransactionOutput};
+use super::{
+    lock_time::SEQUENCE_FINAL, Blockchain, Branch, Script, Transaction, TransactionOutput,
+};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct TransactionInput {
     pub prev_transaction_hash: Vec<u8>,
     pub prev_transaction_index: u32,
-    pub payer: Option<RsaPublicKey>,
-    pub signature: Vec<u8>,
-}
-
-#[derive(Serialize)]
-struct TransactionInputForCheck {
-    pub prev_transaction_hash: Vec<u8>,
-    pub prev_transaction_index: u32,
-    pub payer: Option<RsaPublicKey>,
+    /// runs before the locking script of the output it spends
+    pub unlocking_script: Script,
+    /// the relative lock time, see RelativeLock
+    pub sequence: u32,
 }
 
 impl TransactionInput {
-    pub fn new(
-        prev_transaction_hash: Vec<u8>,
-        prev_transaction_index: u32,
-        payer: Option<RsaPublicKey>,
-        sign_key: &RsaPrivateKey,
-    ) -> Self {
-        let mut tx_in = TransactionInput {
+    /// an input without a relative lock time, it has to be unlocked once the transaction is complete
+    pub fn new(prev_transaction_hash: Vec<u8>, prev_transaction_index: u32) -> Self {
+        Self {
             prev_transaction_hash,
             prev_transaction_index,
-            payer,
-            signature: vec![],
-        };
-
-        tx_in.signature = sign_key
-            .sign(
-                PaddingScheme::new_pkcs1v15_sign(Some(Hash::SHA2_256)),
-                &tx_in.hash(),
-            )
-            .unwrap();
-
-        tx_in
+            unlocking_script: Script::default(),
+            sequence: SEQUENCE_FINAL,
+        }
     }
 
-    pub fn hash(&self) -> Vec<u8> {
-        sha256(
-            &bincode::serialize(&TransactionInputForCheck {
-                prev_transaction_hash: self.prev_transaction_hash.clone(),
-                prev_transaction_index: self.prev_transaction_index,
-                payer: self.payer.clone(),
-            })
-            .unwrap(),
-        )
+    /// the transaction with the output it spends, if it's in the main chain
+    pub fn get_used_transaction(&self, chain: &Blockchain) -> Option<Transaction> {
+        chain
+            .main_chain()
+            .into_iter()
+            .flat_map(|block| block.transactions)
+            .find(|transaction| transaction.hash() == self.prev_transaction_hash)
     }
 
-    pub fn get_used_tx_out(&self, chain: &Blockchain) -> Option<TransactionOutput> {
-        let all_tx_outs = chain.all_blocks();
-        let all_tx_outs: Vec<_> = all_tx_outs
-            .iter()
-            .flat_map(|block| {
-                block.transactions.iter().flat_map(|transaction| {
-                    let hash = transaction.hash();
-
-                    transaction
-                        .transaction_outputs
-                        .iter()
-                        .enumerate()
-                        .map(move |(i, tx_out)| (hash.clone(), i, tx_out))
-                })
+    /// the output it spends, from a block on the branch or a pending transaction
+    pub fn get_used_tx_out(
+        &self,
+        branch: &Branch,
+        pending: &[Transaction],
+    ) -> Option<TransactionOutput> {
+        branch
+            .transaction(&self.prev_transaction_hash)
+            .or_else(|| {
+                pending
+                    .iter()
+                    .find(|transaction| transaction.hash() == self.prev_transaction_hash)
             })
-            .collect();
-
-        let matching_tx_outs: Vec<_> = all_tx_outs
-            .iter()
-            .filter(|(hash, index, _)| {
-                *hash == self.prev_transaction_hash && *index as u32 == self.prev_transaction_index
+            .and_then(|transaction| {
+                transaction
+                    .transaction_outputs
+                    .get(self.prev_transaction_index as usize)
             })
-            .map(|(_, _, tx_out)| tx_out)
-            .collect();
-
-        if matching_tx_outs.len() != 1 {
-            None
-        } else {
-            Some((*matching_tx_outs[0]).clone())
-        }
-    }
-
-    pub fn verify(&self) -> bool {
-        match &self.payer {
-            Some(verify_key) => verify_key
-                .verify(
-                    PaddingScheme::new_pkcs1v15_sign(Some(Hash::SHA2_256)),
-                    &self.hash(),
-                    &self.signature,
-                )
-                .is_ok(),
-            None => true,
-        }
+            .cloned()
     }
 }
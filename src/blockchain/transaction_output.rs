@@ -1,22 +1,35 @@
 use rsa::RsaPublicKey;
 use serde::{Deserialize, Serialize};
 
+use super::Script;
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct TransactionOutput {
     pub amount: u32,
-    pub payee: RsaPublicKey,
-}
-
-#[derive(Serialize)]
-struct TransactionOutputForCheck {
-    pub amount: u32,
-    pub payee: RsaPublicKey,
+    /// what an input spending the output has to satisfy
+    pub locking_script: Script,
 }
 
 impl TransactionOutput {
+    /// an output the payee can spend with a signature
     pub fn new(amount: u32, payee: RsaPublicKey) -> Self {
-        let tx_out = TransactionOutput { amount, payee };
+        let tx_out = TransactionOutput {
+            amount,
+            locking_script: Script::pay_to_public_key(&payee),
+        };
 
         tx_out
     }
+
+    pub fn with_script(amount: u32, locking_script: Script) -> Self {
+        TransactionOutput {
+            amount,
+            locking_script,
+        }
+    }
+
+    /// whether the key alone can spend the output
+    pub fn is_paid_to(&self, public_key: &RsaPublicKey) -> bool {
+        self.locking_script == Script::pay_to_public_key(public_key)
+    }
 }
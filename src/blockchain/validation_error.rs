@@ -0,0 +1,308 @@
+use std::fmt::{self, Display, Formatter};
+
+use crate::consts::MAX_BAN_SCORE;
+
+use super::RelativeLock;
+
+/// why a transaction was rejected
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransactionError {
+    TooBig {
+        max: usize,
+    },
+    TooManyInputs {
+        max: usize,
+    },
+    TooManyOutputs {
+        max: usize,
+    },
+    /// valid, but we don't relay or mine it
+    NonStandard(String),
+    NoInputs,
+    DuplicateInput,
+    /// the input at the index spends an output which doesn't exist
+    MissingInput {
+        index: usize,
+    },
+    Overspend {
+        inputs: u32,
+        outputs: u32,
+    },
+    /// the inputs' or the outputs' amounts add up to more than an amount can be
+    AmountOverflow,
+    /// the scripts of the input at the index don't allow spending the output
+    Script {
+        index: usize,
+        error: ScriptError,
+    },
+    AlreadySpent,
+    ImmatureCoinbase,
+    Locked {
+        lock_time: u64,
+    },
+    /// the input at the index spends an output which isn't in the chain for long enough
+    RelativeLocked {
+        index: usize,
+        relative_lock: RelativeLock,
+    },
+}
+
+impl TransactionError {
+    /// how much a peer sending the transaction misbehaved,
+    /// honest peers may send transactions which are only rejected because our mempool differs
+    pub fn ban_score(&self) -> u32 {
+        match self {
+            TransactionError::NonStandard(_)
+            | TransactionError::MissingInput { .. }
+            | TransactionError::AlreadySpent
+            | TransactionError::ImmatureCoinbase
+            | TransactionError::Locked { .. }
+            | TransactionError::RelativeLocked { .. } => 0,
+            _ => MAX_BAN_SCORE,
+        }
+    }
+}
+
+impl Display for TransactionError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            TransactionError::TooBig { max } => {
+                write!(f, "the transaction is bigger than {} bytes", max)
+            }
+            TransactionError::TooManyInputs { max } => {
+                write!(f, "the transaction has more than {} inputs", max)
+            }
+            TransactionError::TooManyOutputs { max } => {
+                write!(f, "the transaction has more than {} outputs", max)
+            }
+            TransactionError::NonStandard(reason) => write!(f, "{}", reason),
+            TransactionError::NoInputs => write!(f, "the transaction has no inputs"),
+            TransactionError::DuplicateInput => {
+                write!(f, "the transaction spends an output twice")
+            }
+            TransactionError::MissingInput { index } => {
+                write!(f, "input {} spends an output which doesn't exist", index)
+            }
+            TransactionError::Overspend { inputs, outputs } => write!(
+                f,
+                "the transaction spends {} eincoin but only has {}",
+                outputs, inputs
+            ),
+            TransactionError::AmountOverflow => {
+                write!(f, "the transaction's amounts overflow when added up")
+            }
+            TransactionError::Script { index, error } => write!(f, "input {}: {}", index, error),
+            TransactionError::AlreadySpent => {
+                write!(f, "the transaction spends outputs which are already spent")
+            }
+            TransactionError::ImmatureCoinbase => write!(
+                f,
+                "the transaction spends coinbase outputs which aren't mature"
+            ),
+            TransactionError::Locked { lock_time } => {
+                write!(f, "the transaction is locked until {}", lock_time)
+            }
+            TransactionError::RelativeLocked {
+                index,
+                relative_lock,
+            } => write!(
+                f,
+                "input {} can only be mined {} after the output it spends",
+                index, relative_lock
+            ),
+        }
+    }
+}
+
+/// why a block was rejected
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BlockError {
+    /// the block it builds on isn't in the chain
+    UnknownParent,
+    WrongPrevHash,
+    AlreadyKnown,
+    WrongGenesis {
+        network: String,
+    },
+    CheckpointMismatch {
+        height: u64,
+    },
+    ForkBelowCheckpoint {
+        checkpoint_height: u64,
+    },
+    BadProofOfWork,
+    TooOld {
+        median_time_past: u128,
+    },
+    TooFarInFuture,
+    TooBig {
+        max: usize,
+    },
+    TooManySigops {
+        max: usize,
+    },
+    NoCoinbase,
+    ExtraCoinbase,
+    /// the coinbase has to carry the height of its block
+    WrongCoinbaseHeight {
+        height: u64,
+    },
+    /// the fees and the subsidy add up to more than an amount can be
+    FeesOverflow,
+    CoinbaseOverpays {
+        paid: u32,
+        allowed: u32,
+    },
+    InvalidTransaction {
+        index: usize,
+        error: TransactionError,
+    },
+}
+
+impl BlockError {
+    /// how much a peer sending the block misbehaved,
+    /// honest peers may send blocks we can't connect yet, whose date is ahead of our clock,
+    /// stale forks below a checkpoint or transactions which only fail on our branch
+    pub fn ban_score(&self) -> u32 {
+        match self {
+            BlockError::UnknownParent
+            | BlockError::AlreadyKnown
+            | BlockError::TooFarInFuture
+            | BlockError::ForkBelowCheckpoint { .. } => 0,
+            BlockError::InvalidTransaction { error, .. } => error.ban_score(),
+            _ => MAX_BAN_SCORE,
+        }
+    }
+}
+
+impl Display for BlockError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            BlockError::UnknownParent => write!(f, "the block before it is unknown"),
+            BlockError::WrongPrevHash => {
+                write!(f, "the block doesn't point to the block before it")
+            }
+            BlockError::AlreadyKnown => write!(f, "the block is already in the chain"),
+            BlockError::WrongGenesis { network } => write!(
+                f,
+                "the genesis block isn't the one of the {} network",
+                network
+            ),
+            BlockError::CheckpointMismatch { height } => {
+                write!(
+                    f,
+                    "the block doesn't match the checkpoint at height {}",
+                    height
+                )
+            }
+            BlockError::ForkBelowCheckpoint { checkpoint_height } => write!(
+                f,
+                "the block forks below the checkpoint at height {}",
+                checkpoint_height
+            ),
+            BlockError::BadProofOfWork => {
+                write!(f, "the block's hash doesn't meet the difficulty")
+            }
+            BlockError::TooOld { median_time_past } => write!(
+                f,
+                "the block's date isn't after the median time past {}",
+                median_time_past
+            ),
+            BlockError::TooFarInFuture => write!(f, "the block's date is too far in the future"),
+            BlockError::TooBig { max } => write!(f, "the block is bigger than {} bytes", max),
+            BlockError::TooManySigops { max } => {
+                write!(f, "the block needs more than {} signature checks", max)
+            }
+            BlockError::NoCoinbase => {
+                write!(f, "the block's last transaction isn't a coinbase")
+            }
+            BlockError::ExtraCoinbase => write!(f, "the block has more than one coinbase"),
+            BlockError::WrongCoinbaseHeight { height } => write!(
+                f,
+                "the coinbase doesn't carry the height of its block ({})",
+                height
+            ),
+            BlockError::FeesOverflow => {
+                write!(f, "the block's fees and subsidy overflow when added up")
+            }
+            BlockError::CoinbaseOverpays { paid, allowed } => write!(
+                f,
+                "the coinbase pays {} eincoin but only {} are allowed",
+                paid, allowed
+            ),
+            BlockError::InvalidTransaction { index, error } => {
+                write!(f, "transaction {}: {}", index, error)
+            }
+        }
+    }
+}
+
+/// why the scripts of an input don't allow spending an output
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScriptError {
+    TooBig {
+        max: usize,
+    },
+    TooManyOps {
+        max: usize,
+    },
+    StackOverflow {
+        max: usize,
+    },
+    ElementTooBig {
+        max: usize,
+    },
+    TooManyKeys {
+        max: usize,
+    },
+    UnlockingNotPushOnly,
+    StackUnderflow,
+    UnbalancedConditional,
+    InvalidNumber,
+    InvalidMultisig,
+    VerifyFailed,
+    BadSignature,
+    LockTime,
+    Sequence,
+    /// the stack doesn't end with only true
+    Failed,
+}
+
+impl Display for ScriptError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            ScriptError::TooBig { max } => write!(f, "a script is bigger than {} bytes", max),
+            ScriptError::TooManyOps { max } => {
+                write!(f, "a script has more than {} operations", max)
+            }
+            ScriptError::StackOverflow { max } => {
+                write!(f, "the stack has more than {} elements", max)
+            }
+            ScriptError::ElementTooBig { max } => {
+                write!(f, "a script pushes more than {} bytes at once", max)
+            }
+            ScriptError::TooManyKeys { max } => {
+                write!(f, "a multisig has more than {} keys", max)
+            }
+            ScriptError::UnlockingNotPushOnly => {
+                write!(f, "the unlocking script doesn't only push data")
+            }
+            ScriptError::StackUnderflow => write!(f, "an operation needs more stack elements"),
+            ScriptError::UnbalancedConditional => write!(f, "an if isn't closed properly"),
+            ScriptError::InvalidNumber => write!(f, "a number is bigger than 8 bytes"),
+            ScriptError::InvalidMultisig => {
+                write!(f, "a multisig requires more signatures than it has keys")
+            }
+            ScriptError::VerifyFailed => write!(f, "a verify failed"),
+            ScriptError::BadSignature => write!(f, "a signature is wrong"),
+            ScriptError::LockTime => {
+                write!(f, "the transaction's lock time is before the script's")
+            }
+            ScriptError::Sequence => write!(
+                f,
+                "the input's relative lock time is shorter than the script's"
+            ),
+            ScriptError::Failed => write!(f, "the scripts don't leave only true on the stack"),
+        }
+    }
+}
@@ -1,10 +1,4 @@
-use std::{
-    fs,
-    path::PathBuf,
-    sync::{Arc, Mutex},
-};
-
-use bus::Bus;
+use std::{fs, path::PathBuf, sync::Arc};
 
 use rand::rngs::OsRng;
 use rsa::{
@@ -13,12 +7,13 @@ use rsa::{
 };
 
 use crate::{
-    consts::KEY_PAIR_LENGTH,
-    networking::{InternalMessage, MessageDest, MessageSource, MessageType},
+    networking::{InternalMessage, MessageDest, MessageSource, MessageType, PeerRegistry},
     util::LogExpect,
 };
 
-use super::{Blockchain, Transaction};
+use super::{
+    Blockchain, MultisigSpend, PartiallySignedTransaction, RelativeLock, Script, Transaction,
+};
 
 #[derive(Clone)]
 pub struct Wallet {
@@ -27,10 +22,10 @@ pub struct Wallet {
 }
 
 impl Wallet {
-    pub fn new_random() -> Self {
+    pub fn new_random(key_pair_length: usize) -> Self {
         let mut rng = OsRng;
 
-        let private_key = RsaPrivateKey::new(&mut rng, KEY_PAIR_LENGTH).unwrap();
+        let private_key = RsaPrivateKey::new(&mut rng, key_pair_length).unwrap();
         let public_key = private_key.to_public_key();
 
         Self {
@@ -64,36 +59,66 @@ impl Wallet {
         (private_key_string, public_key_string)
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn send_money(
         &self,
         amount: u32,
         transaction_fee: u32,
-        payee_public_key: RsaPublicKey,
-        sender: Arc<Mutex<Bus<InternalMessage>>>,
+        payee: Script,
+        lock_time: u64,
+        relative_lock: Option<RelativeLock>,
+        sender: Arc<PeerRegistry>,
         chain: &mut Blockchain,
-    ) -> Result<(), String> {
+    ) -> Result<Transaction, String> {
         let transaction = Transaction::new(
             amount,
             transaction_fee,
             Some(self.clone()),
-            payee_public_key.clone(),
+            payee,
+            lock_time,
+            relative_lock,
             chain,
         )?;
 
-        sender.lock().unwrap().broadcast(InternalMessage::new(
-            MessageType::Transaction(transaction),
+        sender.send(InternalMessage::new(
+            MessageType::Transaction(transaction.clone()),
             MessageSource::Localhost,
             MessageDest::Broadcast,
         ));
 
-        Ok(())
+        Ok(transaction)
+    }
+
+    /// sign the inputs of a transaction prepared without the private key which spend our outputs,
+    /// returns how many
+    pub fn sign_partially_signed(&self, transaction: &mut PartiallySignedTransaction) -> usize {
+        transaction.sign(&self.private_key)
+    }
+
+    /// co-sign a spend of a multisig address with one of its keys
+    pub fn sign_multisig_spend(&self, spend: &mut MultisigSpend) -> Result<(), String> {
+        spend.sign(&self.private_key)
     }
 
     pub fn compute_balance(&self, chain: &mut Blockchain) -> u32 {
         chain
             .utxos
             .iter()
-            .filter(|(_, _, tx_out)| tx_out.payee == self.public_key)
+            .filter(|(_, _, tx_out)| tx_out.is_paid_to(&self.public_key))
+            .map(|(_, _, tx_out)| tx_out.amount)
+            .sum()
+    }
+
+    /// the balance without the coinbase outputs which aren't mature yet
+    pub fn compute_spendable_balance(&self, chain: &Blockchain) -> u32 {
+        let branch = chain.main_branch();
+
+        chain
+            .utxos
+            .iter()
+            .filter(|(hash, _, tx_out)| {
+                tx_out.is_paid_to(&self.public_key) && branch.is_spendable(hash, &chain.params)
+            })
             .map(|(_, _, tx_out)| tx_out.amount)
             .sum()
     }
@@ -1,9 +1,19 @@
 use log::LevelFilter;
 use simplelog::{ColorChoice, CombinedLogger, SharedLogger, TermLogger, TerminalMode, WriteLogger};
-use std::{fs::File, path::PathBuf};
+use std::{
+    fs::File,
+    num::{NonZeroU32, NonZeroU64, NonZeroUsize},
+    path::PathBuf,
+};
 use structopt::StructOpt;
 
-use crate::consts::LOG_CONFIG;
+use crate::{
+    blockchain::{ChainParams, RelativeLock},
+    consts::LOG_CONFIG,
+    networking::{
+        load_allowed_peers, Limits, NetworkingConfig, NodeKey, OverflowPolicy, TransportConfig,
+    },
+};
 
 /// A shitty try at implementing a cryptocurrency
 #[derive(StructOpt, Clone)]
@@ -14,6 +24,39 @@ pub struct CliArgs {
     /// save the log to this file
     #[structopt(short = "f", long, parse(from_os_str))]
     log_file: Option<PathBuf>,
+    /// the network (mainnet, testnet, regtest) or a json file with its chain parameters
+    #[structopt(long, default_value = "mainnet", parse(try_from_str = ChainParams::from_network))]
+    network: ChainParams,
+    /// check all signatures when syncing, even the ones below the assume-valid block
+    #[structopt(long)]
+    full_verification: bool,
+    /// encrypt and authenticate all connections to other nodes
+    #[structopt(short, long)]
+    encrypt: bool,
+    /// The file with this node's private node key (implies --encrypt). Otherwise, a random one is used
+    #[structopt(long, parse(from_os_str))]
+    node_key_file: Option<PathBuf>,
+    /// only accept peers whose node keys are listed in this file (one hex-encoded key per line)
+    #[structopt(long, parse(from_os_str), requires("node-key-file"))]
+    allowed_peers: Option<PathBuf>,
+    /// what to do with a peer whose outgoing queue is full (drop or disconnect)
+    #[structopt(long, default_value = "drop")]
+    queue_overflow: OverflowPolicy,
+    /// the maximum number of inbound connections
+    #[structopt(long, default_value = "125")]
+    max_connections: usize,
+    /// the maximum number of inbound connections from a single ip address
+    #[structopt(long, default_value = "8")]
+    max_connections_per_ip: usize,
+    /// the maximum number of messages per second a peer may send (at least 1)
+    #[structopt(long, default_value = "1000")]
+    max_messages_per_second: NonZeroU32,
+    /// the maximum number of bytes per second a peer may send (at least 1)
+    #[structopt(long, default_value = "10000000")]
+    max_bytes_per_second: NonZeroU64,
+    /// the maximum number of peers which get the full chain at the same time (at least 1)
+    #[structopt(long, default_value = "4")]
+    max_syncs: NonZeroUsize,
     #[structopt(subcommand)]
     pub subcommand: Command,
 }
@@ -25,6 +68,11 @@ pub enum Command {
         /// Pass a file if you want to save the keypair in a file. Otherwise, it will print to stdout
         file: Option<String>,
     },
+    /// Generate a node key for encrypted connections
+    GenNodeKey {
+        /// Pass a file if you want to save the node key in a file. Otherwise, it will print to stdout
+        file: Option<String>,
+    },
     /// Generate your public key from your private key
     GenPubKey {
         /// The file with your wallet's private key
@@ -49,6 +97,15 @@ pub enum Command {
         /// Attempt to mine new blocks
         #[structopt(short, long, requires("private-key-file"))]
         miner: bool,
+        /// The number of threads to mine with
+        #[structopt(short, long, default_value = "1")]
+        threads: usize,
+        /// Open a mining pool for remote workers on this port
+        #[structopt(long, requires("private-key-file"), conflicts_with("miner"))]
+        pool: Option<String>,
+        /// The number of null bytes the hash of a pool share has to start with
+        #[structopt(long, default_value = "1")]
+        share_difficulty: usize,
         /// The file with your wallet's private key
         #[structopt(short = "k", long = "key-file", parse(from_os_str))]
         private_key_file: Option<PathBuf>,
@@ -56,6 +113,22 @@ pub enum Command {
         #[structopt(short, long)]
         server: Option<String>,
     },
+    /// Mine for a mining pool without running a full node
+    MineWorker {
+        /// The address of the mining pool
+        addr: String,
+        /// The port of the mining pool
+        #[structopt(short, long, default_value = "3333")]
+        port: String,
+        /// The name the pool counts your shares for
+        worker: String,
+        /// The file with the public key the pool pays your rewards to
+        #[structopt(parse(from_os_str))]
+        payout_public_key: PathBuf,
+        /// The number of threads to mine with
+        #[structopt(short, long, default_value = "1")]
+        threads: usize,
+    },
     /// Start a server node which creates a new blockchain
     Genesis {
         /// The port of the server
@@ -64,6 +137,37 @@ pub enum Command {
         /// The file with your wallet's private key
         #[structopt(parse(from_os_str))]
         private_key_file: PathBuf,
+        /// The genesis spec to build the genesis block from. Otherwise, a new one pays the initial coins to your wallet
+        #[structopt(long, parse(from_os_str))]
+        spec: Option<PathBuf>,
+    },
+    /// Create a genesis spec for a new network
+    CreateGenesis {
+        /// The file to write the genesis spec to
+        #[structopt(parse(from_os_str))]
+        file: PathBuf,
+        /// The coins of the genesis block as <public key file>:<amount>
+        #[structopt(required = true)]
+        allocations: Vec<String>,
+        /// A message to put in the genesis block
+        #[structopt(short, long, default_value = "")]
+        message: String,
+        /// The date of the genesis block in milliseconds since the unix epoch. Otherwise, now
+        #[structopt(short, long)]
+        date: Option<u128>,
+    },
+    /// Mine blocks right away on a network which generates blocks on demand (regtest)
+    Generate {
+        /// The address of the eincoin server to connect to
+        addr: String,
+        /// The port of the server
+        #[structopt(short, long, default_value = "3333")]
+        port: String,
+        /// The number of blocks to generate
+        count: usize,
+        /// The file with the public key the blocks pay to
+        #[structopt(parse(from_os_str))]
+        payee_public_key: PathBuf,
     },
     /// Init a transaction on the eincoin network
     Transaction {
@@ -74,7 +178,7 @@ pub enum Command {
         port: String,
         /// The amount of Eincoin to send
         amount: u32,
-        /// The file with the payee's public key
+        /// The file with the payee's public key or multisig address
         #[structopt(parse(from_os_str))]
         payee_public_key: PathBuf,
         /// The file with your wallet's private key
@@ -83,6 +187,204 @@ pub enum Command {
         /// the transaction fee (for faster validation)
         #[structopt(short = "f", long, default_value = "0")]
         transaction_fee: u32,
+        /// The transaction can only be mined after this block height, or this unix timestamp if it's at least 500000000
+        #[structopt(long, default_value = "0")]
+        lock_time: u64,
+        /// The outputs the transaction spends have to be in the chain for this many blocks (or seconds, like 3600s) first
+        #[structopt(long)]
+        relative_lock: Option<RelativeLock>,
+    },
+    /// Prepare a transaction from your wallet with only its public key, to sign it offline
+    PrepareTransaction {
+        /// The address of the eincoin server to connect to
+        addr: String,
+        /// The port of the server
+        #[structopt(short, long, default_value = "3333")]
+        port: String,
+        /// The amount of Eincoin to send
+        amount: u32,
+        /// The file with the payee's public key or multisig address
+        #[structopt(parse(from_os_str))]
+        payee: PathBuf,
+        /// The file with your wallet's public key
+        #[structopt(parse(from_os_str))]
+        public_key_file: PathBuf,
+        /// The file to write the transaction to
+        #[structopt(parse(from_os_str))]
+        transaction_file: PathBuf,
+        /// the transaction fee (for faster validation)
+        #[structopt(short = "f", long, default_value = "0")]
+        transaction_fee: u32,
+        /// The transaction can only be mined after this block height, or this unix timestamp if it's at least 500000000
+        #[structopt(long, default_value = "0")]
+        lock_time: u64,
+        /// The outputs the transaction spends have to be in the chain for this many blocks (or seconds, like 3600s) first
+        #[structopt(long)]
+        relative_lock: Option<RelativeLock>,
+    },
+    /// Sign a prepared transaction with your wallet's private key (works offline)
+    Sign {
+        /// The file with the prepared transaction, the signatures are added to it
+        #[structopt(parse(from_os_str))]
+        transaction_file: PathBuf,
+        /// The file with your wallet's private key
+        #[structopt(parse(from_os_str))]
+        private_key_file: PathBuf,
+        /// Sign without asking after showing what the transaction pays
+        #[structopt(short, long)]
+        yes: bool,
+    },
+    /// Send a prepared transaction once it's signed
+    Broadcast {
+        /// The address of the eincoin server to connect to
+        addr: String,
+        /// The port of the server
+        #[structopt(short, long, default_value = "3333")]
+        port: String,
+        /// The file with the signed transaction
+        #[structopt(parse(from_os_str))]
+        transaction_file: PathBuf,
+    },
+    /// Create a multisig address whose coins can only be spent with signatures of several keys
+    CreateMultisig {
+        /// The file to write the multisig address to
+        #[structopt(parse(from_os_str))]
+        file: PathBuf,
+        /// The number of signatures needed to spend the coins
+        required: usize,
+        /// The files with the co-signers' public keys
+        #[structopt(required = true, parse(from_os_str))]
+        public_key_files: Vec<PathBuf>,
+    },
+    /// View the balance of a multisig address
+    MultisigBalance {
+        /// The address of the eincoin server to connect to
+        addr: String,
+        /// The port of the server
+        #[structopt(short, long, default_value = "3333")]
+        port: String,
+        /// The file with the multisig address
+        #[structopt(parse(from_os_str))]
+        multisig_file: PathBuf,
+    },
+    /// Create an unsigned spend of a multisig address for the co-signers to sign
+    MultisigSpend {
+        /// The address of the eincoin server to connect to
+        addr: String,
+        /// The port of the server
+        #[structopt(short, long, default_value = "3333")]
+        port: String,
+        /// The file with the multisig address
+        #[structopt(parse(from_os_str))]
+        multisig_file: PathBuf,
+        /// The amount of Eincoin to send
+        amount: u32,
+        /// The file with the payee's public key or multisig address
+        #[structopt(parse(from_os_str))]
+        payee: PathBuf,
+        /// The file to write the spend to
+        #[structopt(parse(from_os_str))]
+        spend_file: PathBuf,
+        /// the transaction fee (for faster validation)
+        #[structopt(short = "f", long, default_value = "0")]
+        transaction_fee: u32,
+    },
+    /// Sign a spend of a multisig address as one of its co-signers (works offline)
+    MultisigSign {
+        /// The file with the spend, the signature is added to it
+        #[structopt(parse(from_os_str))]
+        spend_file: PathBuf,
+        /// The file with your wallet's private key
+        #[structopt(parse(from_os_str))]
+        private_key_file: PathBuf,
+        /// Sign without asking after showing what the spend pays
+        #[structopt(short, long)]
+        yes: bool,
+    },
+    /// Send a spend of a multisig address once enough co-signers signed it
+    MultisigBroadcast {
+        /// The address of the eincoin server to connect to
+        addr: String,
+        /// The port of the server
+        #[structopt(short, long, default_value = "3333")]
+        port: String,
+        /// The file with the signed spend
+        #[structopt(parse(from_os_str))]
+        spend_file: PathBuf,
+    },
+    /// Lock coins in a hash time-locked contract (HTLC), e.g. for an atomic swap
+    CreateHtlc {
+        /// The address of the eincoin server to connect to
+        addr: String,
+        /// The port of the server
+        #[structopt(short, long, default_value = "3333")]
+        port: String,
+        /// The amount of Eincoin to lock
+        amount: u32,
+        /// The file with the public key of the recipient, who can redeem it with the preimage
+        #[structopt(parse(from_os_str))]
+        recipient_public_key: PathBuf,
+        /// The block height, or unix timestamp if it's at least 500000000, after which you can refund it
+        timeout: u64,
+        /// The file with your wallet's private key
+        #[structopt(parse(from_os_str))]
+        private_key_file: PathBuf,
+        /// The file to write the HTLC to
+        #[structopt(parse(from_os_str))]
+        htlc_file: PathBuf,
+        /// The hex-encoded sha256 hash to lock to (of the other side of the swap). Otherwise, a new preimage is generated
+        #[structopt(long)]
+        hash: Option<String>,
+        /// the transaction fee (for faster validation)
+        #[structopt(short = "f", long, default_value = "0")]
+        transaction_fee: u32,
+    },
+    /// Take the coins of an HTLC as its recipient, revealing the preimage
+    RedeemHtlc {
+        /// The address of the eincoin server to connect to
+        addr: String,
+        /// The port of the server
+        #[structopt(short, long, default_value = "3333")]
+        port: String,
+        /// The file with the HTLC
+        #[structopt(parse(from_os_str))]
+        htlc_file: PathBuf,
+        /// The hex-encoded preimage of the HTLC's hash
+        preimage: String,
+        /// The file with your wallet's private key
+        #[structopt(parse(from_os_str))]
+        private_key_file: PathBuf,
+        /// the transaction fee (for faster validation), paid from the HTLC's coins
+        #[structopt(short = "f", long, default_value = "0")]
+        transaction_fee: u32,
+    },
+    /// Take the coins of an HTLC back as its sender once its timeout passed
+    RefundHtlc {
+        /// The address of the eincoin server to connect to
+        addr: String,
+        /// The port of the server
+        #[structopt(short, long, default_value = "3333")]
+        port: String,
+        /// The file with the HTLC
+        #[structopt(parse(from_os_str))]
+        htlc_file: PathBuf,
+        /// The file with your wallet's private key
+        #[structopt(parse(from_os_str))]
+        private_key_file: PathBuf,
+        /// the transaction fee (for faster validation), paid from the HTLC's coins
+        #[structopt(short = "f", long, default_value = "0")]
+        transaction_fee: u32,
+    },
+    /// Find the preimage the recipient of an HTLC revealed by redeeming it
+    ExtractPreimage {
+        /// The address of the eincoin server to connect to
+        addr: String,
+        /// The port of the server
+        #[structopt(short, long, default_value = "3333")]
+        port: String,
+        /// The file with the HTLC
+        #[structopt(parse(from_os_str))]
+        htlc_file: PathBuf,
     },
     /// View your wallet's balance
     Balance {
@@ -108,6 +410,17 @@ pub enum Command {
     },
 }
 
+/// the chain parameters of the network, as far as the flags change them
+pub fn chain_params(cli_args: &CliArgs) -> ChainParams {
+    let mut params = cli_args.network.clone();
+
+    if cli_args.full_verification {
+        params.assume_valid = None;
+    }
+
+    params
+}
+
 pub fn setup_loggers(cli_args: &CliArgs) {
     let mut loggers: Vec<Box<dyn SharedLogger>> = vec![TermLogger::new(
         cli_args.log_level,
@@ -126,3 +439,31 @@ pub fn setup_loggers(cli_args: &CliArgs) {
 
     CombinedLogger::init(loggers).unwrap();
 }
+
+pub fn networking_config(cli_args: &CliArgs) -> NetworkingConfig {
+    NetworkingConfig {
+        transport: transport_config(cli_args),
+        overflow_policy: cli_args.queue_overflow,
+        limits: Limits {
+            max_inbound_connections: cli_args.max_connections,
+            max_connections_per_ip: cli_args.max_connections_per_ip,
+            max_messages_per_second: cli_args.max_messages_per_second.get(),
+            max_bytes_per_second: cli_args.max_bytes_per_second.get(),
+            max_concurrent_syncs: cli_args.max_syncs.get(),
+        },
+    }
+}
+
+fn transport_config(cli_args: &CliArgs) -> TransportConfig {
+    if !cli_args.encrypt && cli_args.node_key_file.is_none() {
+        return TransportConfig::Plain;
+    }
+
+    TransportConfig::Noise {
+        node_key: match &cli_args.node_key_file {
+            Some(path) => NodeKey::new_from_keyfile(path.clone()),
+            None => NodeKey::new_random(),
+        },
+        allowed_peers: cli_args.allowed_peers.clone().map(load_allowed_peers),
+    }
+}
@@ -1,20 +1,27 @@
 use std::{path::PathBuf, process::exit};
 
 use crate::{
-    blockchain::{Blockchain, Wallet},
-    networking::{NetworkingManager, NodeMiddleware},
+    blockchain::{Blockchain, ChainParams, Wallet},
+    networking::{NetworkingConfig, NetworkingManager, NodeMiddleware},
 };
 
-pub fn balance(addr: String, port: String, private_key_file: PathBuf) {
+pub fn balance(
+    addr: String,
+    port: String,
+    private_key_file: PathBuf,
+    params: ChainParams,
+    config: NetworkingConfig,
+) {
     let wallet = Wallet::new_from_keyfile(private_key_file);
-    let mut chain = Blockchain::new_empty();
+    let mut chain = Blockchain::new_empty(params);
 
-    let mut networking_manager = NetworkingManager::new(Some(addr + ":" + &port), None);
+    let mut networking_manager = NetworkingManager::new(Some(addr + ":" + &port), None, config);
 
-    networking_manager.add_middleware(NodeMiddleware::new(false, false, move |_, _, chain| {
+    networking_manager.add_middleware(NodeMiddleware::new(false, move |_, _, chain| {
         println!(
-            "Your wallet's current balance is: {}",
-            wallet.compute_balance(chain)
+            "Your wallet's current balance is: {} ({} spendable)",
+            wallet.compute_balance(chain),
+            wallet.compute_spendable_balance(chain)
         );
         exit(0);
     }));
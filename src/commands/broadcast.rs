@@ -0,0 +1,92 @@
+use std::{path::PathBuf, process::exit, thread, time::Duration};
+
+use log::info;
+
+use crate::{
+    blockchain::{Blockchain, ChainParams, PartiallySignedTransaction, Transaction},
+    networking::{
+        InternalMessage, MessageDest, MessageSource, MessageType, NetworkingConfig,
+        NetworkingManager, NodeMiddleware,
+    },
+    util::{to_hex, LogExpect},
+};
+
+/// send a prepared transaction once all its inputs are signed
+pub fn broadcast(
+    addr: String,
+    port: String,
+    transaction_file: PathBuf,
+    params: ChainParams,
+    config: NetworkingConfig,
+) {
+    let transaction = PartiallySignedTransaction::from_file(&transaction_file)
+        .log_expect("Invalid partially signed transaction")
+        .finalize()
+        .log_expect("The transaction can't be sent yet");
+
+    send_transaction(addr, port, transaction, params, config);
+}
+
+/// send a complete transaction after checking it against the server's chain
+pub(super) fn send_transaction(
+    addr: String,
+    port: String,
+    transaction: Transaction,
+    params: ChainParams,
+    config: NetworkingConfig,
+) {
+    build_and_send_transaction(
+        addr,
+        port,
+        move |_| transaction.clone(),
+        |_| {},
+        params,
+        config,
+    );
+}
+
+/// like send_transaction, but the transaction is built once the server's chain is received,
+/// sent is called once it's checked and sent
+pub(super) fn build_and_send_transaction(
+    addr: String,
+    port: String,
+    mut build: impl FnMut(&mut Blockchain) -> Transaction + 'static,
+    mut sent: impl FnMut(&Transaction) + 'static,
+    params: ChainParams,
+    config: NetworkingConfig,
+) {
+    let mut chain = Blockchain::new_empty(params);
+
+    let mut networking_manager = NetworkingManager::new(Some(addr + ":" + &port), None, config);
+
+    networking_manager.add_middleware(NodeMiddleware::new(false, move |_, sender, chain| {
+        let transaction = build(chain);
+        let branch = chain.main_branch();
+        transaction
+            .check_standard(&chain.params)
+            .and_then(|_| transaction.verify(chain, &branch, &[]))
+            .and_then(|_| transaction.verify_locks(&branch, &chain.params))
+            .log_expect("The transaction is invalid");
+
+        sender.send(InternalMessage::new(
+            MessageType::Transaction(transaction.clone()),
+            MessageSource::Localhost,
+            MessageDest::Broadcast,
+        ));
+        info!("Sent the transaction {}", to_hex(&transaction.hash()));
+        sent(&transaction);
+        exit_after_sending();
+    }));
+
+    networking_manager.start_networking(&mut chain);
+}
+
+/// exit a command once it sent its message to the node
+pub(super) fn exit_after_sending() {
+    // todo: find a better way than that
+    // (waiting in another thread, so the node's rejection still arrives)
+    thread::spawn(|| {
+        thread::sleep(Duration::from_secs(1));
+        exit(0);
+    });
+}
@@ -0,0 +1,77 @@
+use std::{
+    fs::{read_to_string, write},
+    path::PathBuf,
+};
+
+use log::info;
+use rsa::{pkcs8::FromPublicKey, RsaPublicKey};
+
+use crate::{
+    blockchain::{ChainParams, GenesisAllocation, GenesisSpec},
+    util::{time_since_unix_epoch, to_hex, LogExpect},
+};
+
+/// write a genesis spec whose block meets the difficulty of the network
+pub fn create_genesis(
+    file: PathBuf,
+    allocations: Vec<String>,
+    message: String,
+    date: Option<u128>,
+    params: ChainParams,
+) {
+    let allocations = allocations
+        .iter()
+        .map(|allocation| parse_allocation(allocation))
+        .collect::<Result<_, String>>()
+        .log_expect("Invalid allocation");
+
+    let mut spec = GenesisSpec {
+        date: date.unwrap_or_else(time_since_unix_epoch),
+        nonce: 0,
+        message,
+        allocations,
+    };
+    let mut block = spec.build().log_expect("Failed to build the genesis block");
+
+    info!("Searching a nonce for the genesis block");
+    while !block.verify_nonce(&params) {
+        block.nonce += 1;
+    }
+    spec.nonce = block.nonce;
+
+    write(&file, serde_json::to_string_pretty(&spec).unwrap())
+        .log_expect(&format!("Failed to write the genesis spec to {:?}", file));
+
+    info!("Wrote the genesis spec to {:?}", file);
+    info!(
+        "Pin the genesis block with \"genesis_hash\": \"{}\" in the chain parameters",
+        to_hex(&block.hash())
+    );
+}
+
+/// <public key file>:<amount>
+fn parse_allocation(allocation: &str) -> Result<GenesisAllocation, String> {
+    let (payee_public_key, amount) = allocation
+        .rsplit_once(':')
+        .ok_or(format!("{:?} isn't <public key file>:<amount>", allocation))?;
+
+    let payee = read_to_string(payee_public_key).map_err(|err| {
+        format!(
+            "Failed to read the key from {:?}: {}",
+            payee_public_key, err
+        )
+    })?;
+    RsaPublicKey::from_public_key_pem(&payee).map_err(|_| {
+        format!(
+            "{:?} is not a PEM-encoded public key file",
+            payee_public_key
+        )
+    })?;
+
+    Ok(GenesisAllocation {
+        payee,
+        amount: amount
+            .parse()
+            .map_err(|_| format!("{:?} is not an amount of eincoin", amount))?,
+    })
+}
@@ -0,0 +1,93 @@
+use std::{fs::read_to_string, path::PathBuf, process::exit};
+
+use log::{error, info};
+use rand::random;
+use rsa::{pkcs8::FromPublicKey, RsaPublicKey};
+
+use crate::{
+    blockchain::{ChainParams, Htlc, Script, Transaction, Wallet, HTLC_PREIMAGE_SIZE},
+    networking::NetworkingConfig,
+    util::{from_hex, sha256, to_hex, LogExpect},
+};
+
+use super::broadcast::build_and_send_transaction;
+
+/// lock coins to a hash and write the contract for the recipient,
+/// without a hash a new preimage is generated
+#[allow(clippy::too_many_arguments)]
+pub fn create_htlc(
+    addr: String,
+    port: String,
+    amount: u32,
+    recipient_public_key: PathBuf,
+    timeout: u64,
+    private_key_file: PathBuf,
+    htlc_file: PathBuf,
+    hash: Option<String>,
+    transaction_fee: u32,
+    params: ChainParams,
+    config: NetworkingConfig,
+) {
+    let wallet = Wallet::new_from_keyfile(private_key_file);
+    let recipient =
+        RsaPublicKey::from_public_key_pem(&read_to_string(&recipient_public_key).log_expect(
+            &format!("Failed to read the key from {:?}", &recipient_public_key),
+        ))
+        .log_expect(&format!(
+            "{:?} is not a PEM-encoded public key file",
+            &recipient_public_key
+        ));
+
+    let hash = match hash {
+        Some(hash) => {
+            let hash = from_hex(&hash).log_expect("Invalid hash");
+            if hash.len() != 32 {
+                error!("The hash isn't a sha256 hash");
+                exit(1);
+            }
+            hash
+        }
+        None => {
+            let preimage: [u8; HTLC_PREIMAGE_SIZE] = random();
+            // on stdout, so it isn't lost with the log
+            println!("{}", to_hex(&preimage));
+            info!("Generated the preimage above, keep it secret until you redeem the other side of the swap");
+            sha256(&preimage)
+        }
+    };
+
+    let locking_script = Script::htlc(&hash, &recipient, &wallet.public_key, timeout);
+    let sender = wallet.public_key.clone();
+
+    build_and_send_transaction(
+        addr,
+        port,
+        move |chain| {
+            Transaction::new(
+                amount,
+                transaction_fee,
+                Some(wallet.clone()),
+                locking_script.clone(),
+                0,
+                None,
+                chain,
+            )
+            .log_expect("Failed to create the transaction")
+        },
+        // only once it's sent, so there is no contract for coins which were never locked
+        move |transaction| {
+            let htlc = Htlc::new(&hash, &recipient, &sender, timeout, transaction)
+                .log_expect("Failed to create the HTLC");
+            htlc.write(&htlc_file).log_expect("Failed to save the HTLC");
+
+            info!(
+                "Locked {} eincoin to the hash {}, the HTLC is in {:?}",
+                amount,
+                to_hex(&hash),
+                htlc_file
+            );
+        },
+        params,
+        config,
+    );
+}
@@ -0,0 +1,36 @@
+use std::{fs::read_to_string, path::PathBuf};
+
+use log::info;
+use rsa::{pkcs8::FromPublicKey, RsaPublicKey};
+
+use crate::{blockchain::MultisigAddress, util::LogExpect};
+
+/// write a multisig address the co-signers can share
+pub fn create_multisig(file: PathBuf, required: usize, public_key_files: Vec<PathBuf>) {
+    let public_keys: Vec<_> = public_key_files
+        .iter()
+        .map(|public_key_file| {
+            RsaPublicKey::from_public_key_pem(&read_to_string(public_key_file).log_expect(
+                &format!("Failed to read the key from {:?}", public_key_file),
+            ))
+            .log_expect(&format!(
+                "{:?} is not a PEM-encoded public key file",
+                public_key_file
+            ))
+        })
+        .collect();
+
+    let address = MultisigAddress::new(required, &public_keys)
+        .log_expect("Failed to create the multisig address");
+    address
+        .write(&file)
+        .log_expect("Failed to save the multisig address");
+
+    info!(
+        "Wrote the {} of {} multisig address {} to {:?}",
+        required,
+        public_keys.len(),
+        address.address(),
+        file
+    );
+}
@@ -0,0 +1,46 @@
+use std::{path::PathBuf, process::exit};
+
+use log::error;
+
+use crate::{
+    blockchain::{Blockchain, ChainParams, Htlc},
+    networking::{NetworkingConfig, NetworkingManager, NodeMiddleware},
+    util::{to_hex, LogExpect},
+};
+
+/// find the preimage an HTLC's recipient revealed by redeeming it
+pub fn extract_preimage(
+    addr: String,
+    port: String,
+    htlc_file: PathBuf,
+    params: ChainParams,
+    config: NetworkingConfig,
+) {
+    let htlc = Htlc::from_file(&htlc_file).log_expect("Invalid HTLC");
+    let mut chain = Blockchain::new_empty(params);
+
+    let mut networking_manager = NetworkingManager::new(Some(addr + ":" + &port), None, config);
+
+    networking_manager.add_middleware(NodeMiddleware::new(false, move |_, _, chain| {
+        // the redeeming transaction may not be mined yet
+        let main_chain = chain.main_chain();
+        let preimage = main_chain
+            .iter()
+            .flat_map(|block| block.transactions.iter())
+            .chain(&chain.unmined_transactions)
+            .find_map(|transaction| htlc.extract_preimage(transaction));
+
+        match preimage {
+            Some(preimage) => {
+                println!("The preimage is: {}", to_hex(&preimage));
+                exit(0);
+            }
+            None => {
+                error!("The HTLC wasn't redeemed yet");
+                exit(1);
+            }
+        }
+    }));
+
+    networking_manager.start_networking(&mut chain);
+}
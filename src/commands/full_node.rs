@@ -1,30 +1,74 @@
 use std::path::PathBuf;
 
+use log::info;
+
 use crate::{
-    blockchain::{Blockchain, Wallet},
-    networking::{MinerMiddleware, NetworkingManager, NodeMiddleware, ServerMiddleware},
+    blockchain::{Blockchain, ChainParams, Wallet},
+    networking::{
+        MinerMiddleware, NetworkingConfig, NetworkingManager, NodeMiddleware, PoolMiddleware,
+        ServerMiddleware, TimeMiddleware,
+    },
 };
 
+pub enum Mining {
+    Off,
+    /// mine in-process with the number of threads
+    Solo {
+        threads: usize,
+    },
+    /// let remote workers mine through a pool on the port
+    Pool {
+        port: String,
+        share_difficulty: usize,
+    },
+}
+
 pub fn full_node(
     addr: String,
     port: String,
-    miner: bool,
+    mining: Mining,
     server: Option<String>,
     private_key_file: Option<PathBuf>,
+    params: ChainParams,
+    config: NetworkingConfig,
 ) {
     // its an ordinary client/server
-    let mut chain = Blockchain::new_empty();
+    let mut chain = Blockchain::new_empty(params);
 
-    let mut networking_manager = NetworkingManager::new(Some(addr + ":" + &port), server.clone());
+    let mut networking_manager =
+        NetworkingManager::new(Some(addr + ":" + &port), server.clone(), config);
 
-    networking_manager.add_middleware(NodeMiddleware::new(server.is_some(), miner, |_, _, _| {}));
-    if miner {
-        let wallet = Wallet::new_from_keyfile(private_key_file.unwrap());
-        networking_manager.add_middleware(MinerMiddleware::new(wallet));
+    networking_manager.add_middleware(NodeMiddleware::new(server.is_some(), |_, _, _| {}));
+    networking_manager.add_middleware(TimeMiddleware::default());
+    let mut mining_stats = None;
+    match mining {
+        Mining::Off => {}
+        Mining::Solo { threads } => {
+            let wallet = Wallet::new_from_keyfile(private_key_file.unwrap());
+            let miner_middleware = MinerMiddleware::new(wallet, threads);
+            mining_stats = Some(miner_middleware.stats());
+            networking_manager.add_middleware(miner_middleware);
+        }
+        Mining::Pool {
+            port,
+            share_difficulty,
+        } => {
+            let wallet = Wallet::new_from_keyfile(private_key_file.unwrap());
+            networking_manager.add_middleware(PoolMiddleware::new(wallet, port, share_difficulty));
+        }
     }
     if server.is_some() {
         networking_manager.add_middleware(ServerMiddleware);
     }
 
     networking_manager.start_networking(&mut chain);
+
+    if let Some(stats) = mining_stats {
+        info!(
+            "Mined {} blocks with {} hashes, last at {} H/s",
+            stats.blocks_found(),
+            stats.hashes(),
+            stats.hashrate()
+        );
+    }
 }
@@ -1,13 +1,18 @@
-use crate::blockchain::Wallet;
+use crate::blockchain::{ChainParams, Script, Wallet};
 
 use log::info;
 use std::{fs::write, path::PathBuf};
 
-pub fn gen_key(file: Option<String>) {
+pub fn gen_key(file: Option<String>, params: ChainParams) {
     // just generate the key
     info!("Generating keypair");
-    let wallet = Wallet::new_random();
+    let wallet = Wallet::new_random(params.key_pair_length);
     let (private_key_string, public_key_string) = wallet.to_string();
+    // signers are shown the addresses outputs pay to
+    info!(
+        "The wallet's address is {}",
+        Script::pay_to_public_key(&wallet.public_key).address()
+    );
 
     if let Some(path) = file {
         info!("Writing keypair to file {}", path);
@@ -0,0 +1,25 @@
+use crate::networking::NodeKey;
+
+use log::info;
+use std::{fs::write, path::PathBuf};
+
+pub fn gen_node_key(file: Option<String>) {
+    info!("Generating node key");
+    let node_key = NodeKey::new_random();
+    let (private_key_string, public_key_string) = node_key.to_string();
+
+    if let Some(path) = file {
+        info!("Writing node key to file {}", path);
+        write(
+            PathBuf::from(path.clone() + ".priv.hex"),
+            private_key_string,
+        )
+        .unwrap();
+        write(PathBuf::from(path + ".pub.hex"), public_key_string).unwrap();
+    } else {
+        info!("Printing node key to stdout");
+        println!("{}", private_key_string);
+        println!();
+        println!("{}", public_key_string);
+    }
+}
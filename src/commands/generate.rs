@@ -0,0 +1,76 @@
+use std::{fs::read_to_string, path::PathBuf, process::exit};
+
+use log::{error, info};
+use rand::random;
+use rsa::{pkcs8::FromPublicKey, RsaPublicKey};
+
+use crate::{
+    blockchain::{BlockTemplateBuilder, Blockchain, ChainParams},
+    networking::{
+        InternalMessage, MessageDest, MessageSource, MessageType, NetworkingConfig,
+        NetworkingManager, NodeMiddleware,
+    },
+    util::{to_hex, LogExpect},
+};
+
+use super::broadcast::exit_after_sending;
+
+pub fn generate(
+    addr: String,
+    port: String,
+    count: usize,
+    payee_public_key: PathBuf,
+    params: ChainParams,
+    config: NetworkingConfig,
+) {
+    if !params.generate_on_demand {
+        error!(
+            "The {} network doesn't generate blocks on demand, mine with a full node instead",
+            params.name
+        );
+        exit(1);
+    }
+
+    let payee_public_key =
+        RsaPublicKey::from_public_key_pem(&read_to_string(&payee_public_key).log_expect(&format!(
+            "Failed to read the key from {:?}",
+            &payee_public_key
+        )))
+        .log_expect(&format!(
+            "{:?} is not a PEM-encoded public key file",
+            &payee_public_key
+        ));
+    let template_builder = BlockTemplateBuilder::new(payee_public_key);
+
+    let mut chain = Blockchain::new_empty(params);
+
+    let mut networking_manager = NetworkingManager::new(Some(addr + ":" + &port), None, config);
+
+    networking_manager.add_middleware(NodeMiddleware::new(false, move |_, sender, chain| {
+        for _ in 0..count {
+            // there is always a chain after receiving it
+            let mut block = template_builder.build(chain).unwrap();
+
+            block.set_extra_nonce(random());
+            while !block.verify_nonce(&chain.params) {
+                block.nonce = block.nonce.wrapping_add(1);
+            }
+
+            if let Err(err) = chain.push_block(block.clone()) {
+                error!("Generated a wrong block: {}", err);
+                exit(1);
+            }
+            info!("Generated block {}", to_hex(&block.hash()));
+
+            sender.send(InternalMessage::new(
+                MessageType::MinedBlock(block),
+                MessageSource::Localhost,
+                MessageDest::Broadcast,
+            ));
+        }
+
+        exit_after_sending();
+    }));
+
+    networking_manager.start_networking(&mut chain);
+}
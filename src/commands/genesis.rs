@@ -1,21 +1,55 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, process::exit};
+
+use log::error;
 
 use crate::{
-    blockchain::{Blockchain, Wallet},
-    networking::{GenesisMiddleware, MinerMiddleware, NetworkingManager, ServerMiddleware},
+    blockchain::{Blockchain, ChainParams, GenesisSpec, Wallet},
+    networking::{
+        GenesisMiddleware, MinerMiddleware, NetworkingConfig, NetworkingManager, ServerMiddleware,
+        TimeMiddleware,
+    },
+    util::LogExpect,
 };
 
-pub fn genesis(port: String, private_key_file: PathBuf) {
+pub fn genesis(
+    port: String,
+    private_key_file: PathBuf,
+    spec: Option<PathBuf>,
+    params: ChainParams,
+    config: NetworkingConfig,
+) {
     // its a genesis node setting up a new blockchain
     let wallet = Wallet::new_from_keyfile(private_key_file);
-    let mut chain = Blockchain::new(wallet.public_key.clone());
+    let mut chain = match spec {
+        Some(spec) => {
+            let genesis = GenesisSpec::from_file(&spec)
+                .and_then(|spec| spec.build())
+                .log_expect("Failed to build the genesis block");
+
+            Blockchain::new_from_genesis(params, genesis)
+                .log_expect(&format!("{:?} doesn't match the chain parameters", spec))
+        }
+        None if params.genesis_hash.is_some() => {
+            error!(
+                "The {} network has a pinned genesis block, pass its spec with --spec",
+                params.name
+            );
+            exit(1);
+        }
+        // a throwaway chain paying the initial coins to us
+        None => Blockchain::new(params, wallet.public_key.clone()),
+    };
 
     chain.compute_utxos();
 
-    let mut networking_manager = NetworkingManager::new(None, Some(port));
+    let mut networking_manager = NetworkingManager::new(None, Some(port), config);
 
-    networking_manager.add_middleware(GenesisMiddleware);
-    networking_manager.add_middleware(MinerMiddleware::new(wallet));
+    networking_manager.add_middleware(GenesisMiddleware::default());
+    networking_manager.add_middleware(TimeMiddleware::default());
+    // otherwise blocks are only mined with the generate command
+    if !chain.params.generate_on_demand {
+        networking_manager.add_middleware(MinerMiddleware::new(wallet, 1));
+    }
     networking_manager.add_middleware(ServerMiddleware);
 
     networking_manager.start_networking(&mut chain);
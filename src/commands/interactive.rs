@@ -1,28 +1,33 @@
 use std::{
-    fs::read_to_string,
     io::{stdin, stdout, Write},
-    path::PathBuf,
+    path::{Path, PathBuf},
     process::exit,
     thread,
     time::Duration,
 };
 
 use log::{error, info};
-use rsa::{pkcs8::FromPublicKey, RsaPublicKey};
 
 use crate::{
-    blockchain::{Blockchain, Wallet},
-    networking::{NetworkingManager, NodeMiddleware},
+    blockchain::{payee_from_file, Blockchain, ChainParams, Wallet},
+    networking::{NetworkingConfig, NetworkingManager, NodeMiddleware, TimeMiddleware},
 };
 
-pub fn interactive(addr: String, port: String, private_key_file: PathBuf) {
+pub fn interactive(
+    addr: String,
+    port: String,
+    private_key_file: PathBuf,
+    params: ChainParams,
+    config: NetworkingConfig,
+) {
     // interactive eincoin shell
     let wallet = Wallet::new_from_keyfile(private_key_file);
-    let mut chain = Blockchain::new_empty();
+    let mut chain = Blockchain::new_empty(params);
 
-    let mut networking_manager = NetworkingManager::new(Some(addr + ":" + &port), None);
-    networking_manager.add_middleware(NodeMiddleware::new(false, false, |_, _, _| {}));
-    networking_manager.start_client_server();
+    let mut networking_manager = NetworkingManager::new(Some(addr + ":" + &port), None, config);
+    networking_manager.add_middleware(NodeMiddleware::new(false, |_, _, _| {}));
+    networking_manager.add_middleware(TimeMiddleware::default());
+    networking_manager.start_client_server(&mut chain);
 
     let sender = networking_manager.get_sender();
     let receiver = networking_manager.get_receiver().unwrap();
@@ -33,17 +38,17 @@ pub fn interactive(addr: String, port: String, private_key_file: PathBuf) {
         let mut input = String::new();
         stdin().read_line(&mut input).unwrap();
 
-        let command: Vec<&str> = input.trim().split_whitespace().collect();
+        let command: Vec<&str> = input.split_whitespace().collect();
 
         // do networking stuff after readline
         // while there are still new messages
         // todo: make this in extra thread or so
-        while let Ok(message) = receiver.try_recv() {
-            networking_manager.run_middlewares(message, &mut chain);
+        while let Ok(event) = receiver.try_recv() {
+            networking_manager.handle_event(event, &mut chain);
         }
 
         // error handling for empty line
-        if command.len() == 0 {
+        if command.is_empty() {
             continue;
         }
 
@@ -73,22 +78,10 @@ pub fn interactive(addr: String, port: String, private_key_file: PathBuf) {
                         continue;
                     }
                 };
-                let payee_public_key = match RsaPublicKey::from_public_key_pem(
-                    &match read_to_string(PathBuf::from(command[2])) {
-                        Ok(string) => string,
-                        Err(_) => {
-                            error!("Failed to read the key from {}", command[2]);
-                            continue;
-                        }
-                    },
-                ) {
-                    Ok(key) => key,
+                let payee = match payee_from_file(Path::new(command[2])) {
+                    Ok(payee) => payee,
                     Err(err) => {
-                        error!(
-                            "{:?} is not a PEM-encoded private key file. Most probably you provided a private key file instead: {}",
-                            command[2],
-                            err
-                        );
+                        error!("{}", err);
                         continue;
                     }
                 };
@@ -96,7 +89,9 @@ pub fn interactive(addr: String, port: String, private_key_file: PathBuf) {
                 match wallet.send_money(
                     amount,
                     transaction_fee,
-                    payee_public_key,
+                    payee,
+                    0,
+                    None,
                     sender.clone(),
                     &mut chain,
                 ) {
@@ -114,7 +109,10 @@ pub fn interactive(addr: String, port: String, private_key_file: PathBuf) {
             "clear" => {
                 print!("\x1B[2J");
             }
-            "exit" => exit(0),
+            "exit" => {
+                networking_manager.shutdown(&mut chain);
+                exit(0);
+            }
             _ => error!("Unknown command"),
         }
     }
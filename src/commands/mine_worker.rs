@@ -0,0 +1,229 @@
+use std::{
+    fs::read_to_string,
+    io::{Error, ErrorKind, Result},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    thread,
+    time::Instant,
+};
+
+use log::{debug, info, warn};
+use rsa::{pkcs8::FromPublicKey, RsaPublicKey};
+use tokio::{
+    io::BufReader,
+    net::TcpStream,
+    runtime::Builder,
+    sync::mpsc::{self, UnboundedSender},
+    time::interval,
+};
+
+use crate::{
+    blockchain::BlockHeader,
+    consts::HASHRATE_LOG_INTERVAL,
+    networking::pool::{read_line, write_line, PoolRequest, PoolResponse},
+    util::{from_hex, LogExpect},
+};
+
+// how many hashes a thread tries before adding them to the hash count
+const HASH_BATCH_SIZE: u64 = 1024;
+
+/// mine for a mining pool, the worker only gets block headers, so it doesn't need the chain
+pub fn mine_worker(
+    addr: String,
+    port: String,
+    worker: String,
+    payout_public_key: PathBuf,
+    threads: usize,
+) {
+    let payout_key = read_to_string(&payout_public_key).log_expect(&format!(
+        "Failed to read the key from {:?}",
+        &payout_public_key
+    ));
+    RsaPublicKey::from_public_key_pem(&payout_key).log_expect(&format!(
+        "{:?} is not a PEM-encoded public key file",
+        &payout_public_key
+    ));
+
+    let runtime = Builder::new_current_thread().enable_all().build().unwrap();
+
+    runtime
+        .block_on(work(addr + ":" + &port, worker, payout_key, threads.max(1)))
+        .log_expect("Lost the connection to the pool");
+}
+
+async fn work(addr: String, worker: String, payout_key: String, threads: usize) -> Result<()> {
+    let stream = TcpStream::connect(&addr).await?;
+    info!("Connected to the pool at {}", addr);
+
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+
+    // read in an own task, a line read only partially would get lost when another branch of select! wins
+    let (line_sender, mut lines) = mpsc::channel(1);
+    tokio::spawn(async move {
+        loop {
+            let line = read_line(&mut reader).await.transpose();
+            let stop = !matches!(line, Some(Ok(_)));
+
+            if line_sender.send(line).await.is_err() || stop {
+                break;
+            }
+        }
+    });
+
+    write_line(&mut writer, &PoolRequest::Login { worker, payout_key }).await?;
+
+    let (share_sender, mut shares) = mpsc::unbounded_channel();
+    let hashes = Arc::new(AtomicU64::new(0));
+    let mut killswitch: Option<Arc<AtomicBool>> = None;
+
+    let mut accepted = 0;
+    let mut rejected = 0;
+    let mut blocks = 0;
+
+    let mut log_hashrate = interval(HASHRATE_LOG_INTERVAL);
+    let mut last_hashes = 0;
+    let mut last_time = Instant::now();
+
+    let result = loop {
+        tokio::select! {
+            line = lines.recv() => {
+                let line = match line.flatten() {
+                    Some(Ok(line)) => line,
+                    Some(Err(err)) => break Err(err),
+                    None => {
+                        info!("The pool closed the connection");
+                        break Ok(());
+                    }
+                };
+
+                match serde_json::from_str(&line) {
+                    Ok(PoolResponse::Job { job_id, header, share_difficulty }) => {
+                        let header = match from_hex(&header).and_then(|header| {
+                            bincode::deserialize::<BlockHeader>(&header).map_err(|err| err.to_string())
+                        }) {
+                            Ok(header) => header,
+                            Err(err) => {
+                                warn!("Couldn't decode the header of job {}: {}", job_id, err);
+                                continue;
+                            }
+                        };
+
+                        debug!("Got job {} with share difficulty {}", job_id, share_difficulty);
+
+                        if let Some(killswitch) = killswitch.take() {
+                            killswitch.store(true, Ordering::Relaxed);
+                        }
+                        killswitch = Some(grind(
+                            job_id,
+                            header,
+                            share_difficulty,
+                            threads,
+                            share_sender.clone(),
+                            hashes.clone(),
+                        ));
+                    }
+                    Ok(PoolResponse::Accepted { job_id, block }) => {
+                        accepted += 1;
+
+                        if block {
+                            blocks += 1;
+                            info!("Our share for job {} solved a block", job_id);
+                        }
+                    }
+                    // only a job answers a successful login
+                    Ok(PoolResponse::Rejected { reason }) if killswitch.is_none() => {
+                        break Err(Error::new(
+                            ErrorKind::PermissionDenied,
+                            format!("the pool rejected the login: {}", reason),
+                        ));
+                    }
+                    Ok(PoolResponse::Rejected { reason }) => {
+                        rejected += 1;
+                        debug!("The pool rejected a share: {}", reason);
+                    }
+                    Err(err) => warn!("Couldn't parse a message of the pool: {}", err),
+                }
+            }
+            Some((job_id, nonce)) = shares.recv() => {
+                write_line(&mut writer, &PoolRequest::Submit { job_id, nonce }).await?;
+            }
+            _ = log_hashrate.tick() => {
+                let hashes = hashes.load(Ordering::Relaxed);
+                let hashrate = (hashes - last_hashes) as f64 / last_time.elapsed().as_secs_f64();
+
+                if hashes > 0 {
+                    info!(
+                        "Mining at {:.0} H/s, {} accepted and {} rejected shares, {} blocks",
+                        hashrate, accepted, rejected, blocks
+                    );
+                }
+
+                last_hashes = hashes;
+                last_time = Instant::now();
+            }
+        }
+    };
+
+    if let Some(killswitch) = killswitch {
+        killswitch.store(true, Ordering::Relaxed);
+    }
+
+    result
+}
+
+/// search shares for the header on all threads, thread i tries the nonces i, i + threads, i + 2 * threads, ...
+///
+/// returns the killswitch of the threads
+fn grind(
+    job_id: u64,
+    header: BlockHeader,
+    share_difficulty: usize,
+    threads: usize,
+    share_sender: UnboundedSender<(u64, u64)>,
+    hashes: Arc<AtomicU64>,
+) -> Arc<AtomicBool> {
+    let killswitch = Arc::new(AtomicBool::new(false));
+
+    let step = threads as u64;
+    let first_nonce = header.nonce - header.nonce % step;
+
+    for i in 0..step {
+        let mut header = header.clone();
+        header.nonce = first_nonce.checked_add(i).unwrap_or(i);
+
+        let killswitch = killswitch.clone();
+        let share_sender = share_sender.clone();
+        let hashes = hashes.clone();
+
+        thread::spawn(move || {
+            let mut batch = 0;
+
+            while !killswitch.load(Ordering::Relaxed) {
+                // the same hash as the block's
+                if header.meets_difficulty(share_difficulty)
+                    && share_sender.send((job_id, header.nonce)).is_err()
+                {
+                    break;
+                }
+
+                // the pool sends a new job long before the nonces run out
+                header.nonce = match header.nonce.checked_add(step) {
+                    Some(nonce) => nonce,
+                    None => break,
+                };
+
+                batch += 1;
+                if batch == HASH_BATCH_SIZE {
+                    hashes.fetch_add(batch, Ordering::Relaxed);
+                    batch = 0;
+                }
+            }
+        });
+    }
+
+    killswitch
+}
@@ -1,17 +1,49 @@
 mod balance;
+mod broadcast;
+mod create_genesis;
+mod create_htlc;
+mod create_multisig;
+mod extract_preimage;
 mod full_node;
 mod gen_completions;
 mod gen_key;
+mod gen_node_key;
 mod gen_pub_key;
+mod generate;
 mod genesis;
 mod interactive;
+mod mine_worker;
+mod multisig_balance;
+mod multisig_broadcast;
+mod multisig_sign;
+mod multisig_spend;
+mod prepare_transaction;
+mod redeem_htlc;
+mod refund_htlc;
+mod sign;
 mod transaction;
 
 pub use balance::balance;
-pub use full_node::full_node;
+pub use broadcast::broadcast;
+pub use create_genesis::create_genesis;
+pub use create_htlc::create_htlc;
+pub use create_multisig::create_multisig;
+pub use extract_preimage::extract_preimage;
+pub use full_node::{full_node, Mining};
 pub use gen_completions::gen_completions;
 pub use gen_key::gen_key;
+pub use gen_node_key::gen_node_key;
 pub use gen_pub_key::gen_pub_key;
+pub use generate::generate;
 pub use genesis::genesis;
 pub use interactive::interactive;
+pub use mine_worker::mine_worker;
+pub use multisig_balance::multisig_balance;
+pub use multisig_broadcast::multisig_broadcast;
+pub use multisig_sign::multisig_sign;
+pub use multisig_spend::multisig_spend;
+pub use prepare_transaction::prepare_transaction;
+pub use redeem_htlc::redeem_htlc;
+pub use refund_htlc::refund_htlc;
+pub use sign::sign;
 pub use transaction::transaction;
@@ -0,0 +1,31 @@
+use std::{path::PathBuf, process::exit};
+
+use crate::{
+    blockchain::{Blockchain, ChainParams, MultisigAddress},
+    networking::{NetworkingConfig, NetworkingManager, NodeMiddleware},
+    util::LogExpect,
+};
+
+pub fn multisig_balance(
+    addr: String,
+    port: String,
+    multisig_file: PathBuf,
+    params: ChainParams,
+    config: NetworkingConfig,
+) {
+    let address = MultisigAddress::from_file(&multisig_file).log_expect("Invalid multisig address");
+    let mut chain = Blockchain::new_empty(params);
+
+    let mut networking_manager = NetworkingManager::new(Some(addr + ":" + &port), None, config);
+
+    networking_manager.add_middleware(NodeMiddleware::new(false, move |_, _, chain| {
+        println!(
+            "The multisig address' current balance is: {} ({} spendable)",
+            address.compute_balance(chain),
+            address.compute_spendable_balance(chain)
+        );
+        exit(0);
+    }));
+
+    networking_manager.start_networking(&mut chain);
+}
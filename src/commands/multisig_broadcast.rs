@@ -0,0 +1,25 @@
+use std::path::PathBuf;
+
+use crate::{
+    blockchain::{ChainParams, MultisigSpend},
+    networking::NetworkingConfig,
+    util::LogExpect,
+};
+
+use super::broadcast::send_transaction;
+
+/// send a spend once enough co-signers signed it
+pub fn multisig_broadcast(
+    addr: String,
+    port: String,
+    spend_file: PathBuf,
+    params: ChainParams,
+    config: NetworkingConfig,
+) {
+    let transaction = MultisigSpend::from_file(&spend_file)
+        .log_expect("Invalid spend")
+        .finalize()
+        .log_expect("The spend can't be sent yet");
+
+    send_transaction(addr, port, transaction, params, config);
+}
@@ -0,0 +1,41 @@
+use std::path::PathBuf;
+
+use log::info;
+
+use crate::{
+    blockchain::{MultisigSpend, Wallet},
+    util::LogExpect,
+};
+
+use super::sign::confirm_signing;
+
+/// add a co-signer's signature to a spend, no connection needed
+pub fn multisig_sign(spend_file: PathBuf, private_key_file: PathBuf, yes: bool) {
+    let wallet = Wallet::new_from_keyfile(private_key_file);
+    let mut spend = MultisigSpend::from_file(&spend_file).log_expect("Invalid spend");
+
+    let fee = spend
+        .fee()
+        .ok_or("the outputs get more than the inputs have")
+        .log_expect("Invalid spend");
+    confirm_signing(
+        &spend.transaction,
+        fee,
+        &spend.address.locking_script(),
+        "the multisig address",
+        yes,
+    );
+
+    wallet
+        .sign_multisig_spend(&mut spend)
+        .log_expect("Failed to sign the spend");
+    spend
+        .write(&spend_file)
+        .log_expect("Failed to save the spend");
+
+    info!(
+        "Signed the spend, it has {} of the {} required signatures",
+        spend.signature_count(),
+        spend.address.required()
+    );
+}
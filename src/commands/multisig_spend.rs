@@ -0,0 +1,53 @@
+use std::{path::PathBuf, process::exit};
+
+use log::info;
+
+use crate::{
+    blockchain::{payee_from_file, Blockchain, ChainParams, MultisigAddress, MultisigSpend},
+    networking::{NetworkingConfig, NetworkingManager, NodeMiddleware},
+    util::LogExpect,
+};
+
+/// write an unsigned spend of a multisig address for the co-signers to sign
+#[allow(clippy::too_many_arguments)]
+pub fn multisig_spend(
+    addr: String,
+    port: String,
+    multisig_file: PathBuf,
+    amount: u32,
+    payee: PathBuf,
+    spend_file: PathBuf,
+    transaction_fee: u32,
+    params: ChainParams,
+    config: NetworkingConfig,
+) {
+    let address = MultisigAddress::from_file(&multisig_file).log_expect("Invalid multisig address");
+    let payee = payee_from_file(&payee).log_expect("Invalid payee");
+    let mut chain = Blockchain::new_empty(params);
+
+    let mut networking_manager = NetworkingManager::new(Some(addr + ":" + &port), None, config);
+
+    networking_manager.add_middleware(NodeMiddleware::new(false, move |_, _, chain| {
+        let spend = MultisigSpend::new(
+            address.clone(),
+            amount,
+            transaction_fee,
+            payee.clone(),
+            chain,
+        )
+        .log_expect("Error while creating the spend");
+        spend
+            .write(&spend_file)
+            .log_expect("Failed to save the spend");
+
+        info!(
+            "Wrote the spend of {} eincoin to {:?}, it needs {} signatures",
+            amount,
+            spend_file,
+            address.required()
+        );
+        exit(0);
+    }));
+
+    networking_manager.start_networking(&mut chain);
+}
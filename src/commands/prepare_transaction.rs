@@ -0,0 +1,66 @@
+use std::{fs::read_to_string, path::PathBuf, process::exit};
+
+use log::info;
+use rsa::{pkcs8::FromPublicKey, RsaPublicKey};
+
+use crate::{
+    blockchain::{
+        payee_from_file, Blockchain, ChainParams, PartiallySignedTransaction, RelativeLock,
+    },
+    networking::{NetworkingConfig, NetworkingManager, NodeMiddleware},
+    util::LogExpect,
+};
+
+/// write a transaction from a wallet to be signed offline, only its public key is needed
+#[allow(clippy::too_many_arguments)]
+pub fn prepare_transaction(
+    addr: String,
+    port: String,
+    amount: u32,
+    payee: PathBuf,
+    public_key_file: PathBuf,
+    transaction_file: PathBuf,
+    transaction_fee: u32,
+    lock_time: u64,
+    relative_lock: Option<RelativeLock>,
+    params: ChainParams,
+    config: NetworkingConfig,
+) {
+    let payee = payee_from_file(&payee).log_expect("Invalid payee");
+    let public_key =
+        RsaPublicKey::from_public_key_pem(&read_to_string(&public_key_file).log_expect(&format!(
+            "Failed to read the key from {:?}",
+            &public_key_file
+        )))
+        .log_expect(&format!(
+            "{:?} is not a PEM-encoded public key file",
+            &public_key_file
+        ));
+    let mut chain = Blockchain::new_empty(params);
+
+    let mut networking_manager = NetworkingManager::new(Some(addr + ":" + &port), None, config);
+
+    networking_manager.add_middleware(NodeMiddleware::new(false, move |_, _, chain| {
+        let transaction = PartiallySignedTransaction::new(
+            amount,
+            transaction_fee,
+            &public_key,
+            payee.clone(),
+            lock_time,
+            relative_lock,
+            chain,
+        )
+        .log_expect("Error while preparing the transaction");
+        transaction
+            .write(&transaction_file)
+            .log_expect("Failed to save the transaction");
+
+        info!(
+            "Wrote the transaction of {} eincoin to {:?}, sign it with the private key",
+            amount, transaction_file
+        );
+        exit(0);
+    }));
+
+    networking_manager.start_networking(&mut chain);
+}
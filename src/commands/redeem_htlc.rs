@@ -0,0 +1,32 @@
+use std::path::PathBuf;
+
+use crate::{
+    blockchain::{ChainParams, Htlc, Wallet},
+    networking::NetworkingConfig,
+    util::{from_hex, LogExpect},
+};
+
+use super::broadcast::send_transaction;
+
+/// take the coins of an HTLC as its recipient, revealing the preimage
+#[allow(clippy::too_many_arguments)]
+pub fn redeem_htlc(
+    addr: String,
+    port: String,
+    htlc_file: PathBuf,
+    preimage: String,
+    private_key_file: PathBuf,
+    transaction_fee: u32,
+    params: ChainParams,
+    config: NetworkingConfig,
+) {
+    let wallet = Wallet::new_from_keyfile(private_key_file);
+    let htlc = Htlc::from_file(&htlc_file).log_expect("Invalid HTLC");
+    let preimage = from_hex(&preimage).log_expect("Invalid preimage");
+
+    let transaction = htlc
+        .redeem(&preimage, &wallet.private_key, transaction_fee)
+        .log_expect("Failed to redeem the HTLC");
+
+    send_transaction(addr, port, transaction, params, config);
+}
@@ -0,0 +1,29 @@
+use std::path::PathBuf;
+
+use crate::{
+    blockchain::{ChainParams, Htlc, Wallet},
+    networking::NetworkingConfig,
+    util::LogExpect,
+};
+
+use super::broadcast::send_transaction;
+
+/// take the coins of an HTLC back as its sender, once its timeout passed
+pub fn refund_htlc(
+    addr: String,
+    port: String,
+    htlc_file: PathBuf,
+    private_key_file: PathBuf,
+    transaction_fee: u32,
+    params: ChainParams,
+    config: NetworkingConfig,
+) {
+    let wallet = Wallet::new_from_keyfile(private_key_file);
+    let htlc = Htlc::from_file(&htlc_file).log_expect("Invalid HTLC");
+
+    let transaction = htlc
+        .refund(&wallet.private_key, transaction_fee)
+        .log_expect("Failed to refund the HTLC");
+
+    send_transaction(addr, port, transaction, params, config);
+}
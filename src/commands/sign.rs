@@ -0,0 +1,87 @@
+use std::{
+    io::{stdin, stdout, Write},
+    path::PathBuf,
+    process::exit,
+};
+
+use log::{info, warn};
+
+use crate::{
+    blockchain::{PartiallySignedTransaction, Script, Transaction, Wallet},
+    util::LogExpect,
+};
+
+/// sign a prepared transaction, no connection needed
+pub fn sign(transaction_file: PathBuf, private_key_file: PathBuf, yes: bool) {
+    let wallet = Wallet::new_from_keyfile(private_key_file);
+    let mut transaction = PartiallySignedTransaction::from_file(&transaction_file)
+        .log_expect("Invalid partially signed transaction");
+
+    let fee = transaction
+        .fee()
+        .ok_or("the outputs get more than the inputs have")
+        .log_expect("Invalid partially signed transaction");
+    confirm_signing(
+        &transaction.transaction,
+        fee,
+        &Script::pay_to_public_key(&wallet.public_key),
+        "your wallet",
+        yes,
+    );
+
+    let signed = wallet.sign_partially_signed(&mut transaction);
+    if signed == 0 {
+        warn!("None of the inputs spends outputs of your wallet");
+    }
+    transaction
+        .write(&transaction_file)
+        .log_expect("Failed to save the transaction");
+
+    info!(
+        "Signed {} inputs, {} of {} are signed",
+        signed,
+        transaction.signed_inputs(),
+        transaction.inputs.len()
+    );
+}
+
+/// the signer has to trust the file, so show what it signs and exit unless they agree,
+/// outputs locked by own_script are marked with own_name
+pub(super) fn confirm_signing(
+    transaction: &Transaction,
+    fee: u32,
+    own_script: &Script,
+    own_name: &str,
+    yes: bool,
+) {
+    for tx_out in &transaction.transaction_outputs {
+        let receiver = if tx_out.locking_script == *own_script {
+            format!(" ({})", own_name)
+        } else {
+            String::new()
+        };
+        info!(
+            "The transaction pays {} eincoin to {}{}",
+            tx_out.amount,
+            tx_out.locking_script.address(),
+            receiver
+        );
+    }
+    info!("The transaction fee is {} eincoin", fee);
+
+    if yes {
+        return;
+    }
+
+    print!("Sign it? [y/N] ");
+    stdout().flush().unwrap();
+    let mut answer = String::new();
+    stdin()
+        .read_line(&mut answer)
+        .log_expect("Failed to read the answer");
+
+    if !answer.trim().eq_ignore_ascii_case("y") {
+        info!("Didn't sign the transaction");
+        exit(1);
+    }
+}
@@ -1,14 +1,16 @@
-use std::{fs::read_to_string, path::PathBuf, process::exit, thread, time::Duration};
+use std::path::PathBuf;
 
 use log::info;
-use rsa::{pkcs8::FromPublicKey, RsaPublicKey};
 
 use crate::{
-    blockchain::{Blockchain, Wallet},
-    networking::{NetworkingManager, NodeMiddleware},
+    blockchain::{payee_from_file, Blockchain, ChainParams, RelativeLock, Wallet},
+    networking::{NetworkingConfig, NetworkingManager, NodeMiddleware},
     util::LogExpect,
 };
 
+use super::broadcast::exit_after_sending;
+
+#[allow(clippy::too_many_arguments)]
 pub fn transaction(
     addr: String,
     port: String,
@@ -16,40 +18,33 @@ pub fn transaction(
     payee_public_key: PathBuf,
     private_key_file: PathBuf,
     transaction_fee: u32,
+    lock_time: u64,
+    relative_lock: Option<RelativeLock>,
+    params: ChainParams,
+    config: NetworkingConfig,
 ) {
     let wallet = Wallet::new_from_keyfile(private_key_file);
-    let mut chain = Blockchain::new_empty();
-
-    let mut networking_manager = NetworkingManager::new(Some(addr + ":" + &port), None);
-
-    let payee_public_key = RsaPublicKey::from_public_key_pem(&read_to_string(
-        &payee_public_key,
-    )
-    .log_expect(&format!("Failed to read the key form {:?}", &payee_public_key)))
-    .log_expect(&format!(
-                "{:?} is not a PEM-encoded private key file. Most probably you provided a private key file instead",
-                &payee_public_key
-        ));
-
-    networking_manager.add_middleware(NodeMiddleware::new(
-        false,
-        false,
-        move |_, sender, blockchain| {
-            wallet
-                .send_money(
-                    amount,
-                    transaction_fee,
-                    payee_public_key.clone(),
-                    sender,
-                    blockchain,
-                )
-                .log_expect("Error while sending the money");
-            info!("Sent {} eincoin", amount);
-            // todo: find a better way than that
-            thread::sleep(Duration::from_secs(1));
-            exit(0);
-        },
-    ));
+    let mut chain = Blockchain::new_empty(params);
+
+    let mut networking_manager = NetworkingManager::new(Some(addr + ":" + &port), None, config);
+
+    let payee = payee_from_file(&payee_public_key).log_expect("Invalid payee");
+
+    networking_manager.add_middleware(NodeMiddleware::new(false, move |_, sender, blockchain| {
+        wallet
+            .send_money(
+                amount,
+                transaction_fee,
+                payee.clone(),
+                lock_time,
+                relative_lock,
+                sender,
+                blockchain,
+            )
+            .log_expect("Error while sending the money");
+        info!("Sent {} eincoin", amount);
+        exit_after_sending();
+    }));
 
     networking_manager.start_networking(&mut chain);
 }
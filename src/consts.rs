@@ -1,20 +1,49 @@
+use std::time::Duration;
+
 use lazy_static::lazy_static;
 use log::LevelFilter;
 use simplelog::{Config, ConfigBuilder, LevelPadding};
 
-pub const DIFFICULTY: u32 = 2;
-pub const INITIAL_COIN_AMOUNT: u32 = 100;
-pub const MINING_REWARD: u32 = 1;
-pub const KEY_PAIR_LENGTH: usize = 2048;
+// the consensus rules are in the ChainParams of the network
+// clocks of peers further off than this aren't trusted
+pub const MAX_CLOCK_OFFSET: Duration = Duration::from_secs(70 * 60);
+// the clocks of fewer peers don't adjust ours, a single peer could set it
+pub const MIN_TIME_SAMPLES: usize = 5;
+// how many verified signatures a node remembers
+pub const MAX_SIGNATURE_CACHE_SIZE: usize = 100_000;
+// policy limits, nodes don't relay or mine transactions exceeding them
+pub const MAX_STANDARD_TRANSACTION_SIZE: usize = 100 * 1024;
+pub const MAX_STANDARD_TRANSACTION_SIGOPS: usize = 100;
 
 lazy_static! {
-    // lazily create the needed start for a block hash: a null byte DIFFICULTY times
-    pub static ref NEEDED_HASH_START: Vec<u8> = vec![0; DIFFICULTY as usize];
-
     pub static ref LOG_CONFIG: Config = ConfigBuilder::new()
-    .set_target_level(LevelFilter::Off)
-    .set_level_padding(LevelPadding::Right)
-    .build();
+        .set_target_level(LevelFilter::Off)
+        .set_level_padding(LevelPadding::Right)
+        .build();
 }
-
-pub const BUFFER_SIZE: usize = 4096;
+// the biggest message we accept from a peer (bincode encoded)
+pub const MAX_MESSAGE_SIZE: usize = 32 * 1024 * 1024;
+// how long a peer may take to complete the noise handshake
+pub const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+// how many messages can wait to be sent to a single peer
+pub const PEER_QUEUE_SIZE: usize = 4096;
+// peers misbehaving this much get disconnected and banned
+pub const MAX_BAN_SCORE: u32 = 100;
+pub const BAN_DURATION: Duration = Duration::from_secs(24 * 60 * 60);
+// blocks arriving before their parent are kept this long, but only this many
+pub const ORPHAN_BLOCK_EXPIRY: Duration = Duration::from_secs(20 * 60);
+pub const MAX_ORPHAN_BLOCKS: usize = 100;
+// how often the miner logs its hashrate
+pub const HASHRATE_LOG_INTERVAL: Duration = Duration::from_secs(10);
+// how often the miner's stats thread checks whether the miner was killed
+pub const KILLSWITCH_POLL_INTERVAL: Duration = Duration::from_millis(100);
+// how often the miner updates the timestamp of the block it's mining
+pub const TIMESTAMP_ROLL_INTERVAL: Duration = Duration::from_secs(1);
+// how many of the last shares of a mining pool split the block rewards
+pub const POOL_PAYOUT_WINDOW: usize = 1000;
+// how often pool workers get a new job with a fresh timestamp
+pub const POOL_JOB_REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+// how many of its last jobs a pool worker may still submit shares for, older ones are dropped
+pub const MAX_POOL_JOBS_PER_WORKER: usize = 4;
+// the longest line a pool worker may send
+pub const MAX_POOL_LINE_SIZE: usize = 64 * 1024;
@@ -1,9 +1,12 @@
 use log::info;
 use structopt::StructOpt;
 
-use crate::cli::{setup_loggers, CliArgs, Command};
+use crate::cli::{chain_params, networking_config, setup_loggers, CliArgs, Command};
 use crate::commands::{
-    balance, full_node, gen_completions, gen_key, gen_pub_key, genesis, interactive, transaction,
+    balance, broadcast, create_genesis, create_htlc, create_multisig, extract_preimage, full_node,
+    gen_completions, gen_key, gen_node_key, gen_pub_key, generate, genesis, interactive,
+    mine_worker, multisig_balance, multisig_broadcast, multisig_sign, multisig_spend,
+    prepare_transaction, redeem_htlc, refund_htlc, sign, transaction, Mining,
 };
 
 mod blockchain;
@@ -20,9 +23,15 @@ fn main() {
 
     info!("Started eincoin node");
 
+    let config = networking_config(&cli_args);
+    let params = chain_params(&cli_args);
+
     match cli_args.subcommand {
         Command::GenKey { file } => {
-            gen_key(file);
+            gen_key(file, params);
+        }
+        Command::GenNodeKey { file } => {
+            gen_node_key(file);
         }
         Command::GenPubKey { private_key_file } => {
             gen_pub_key(private_key_file);
@@ -34,16 +43,54 @@ fn main() {
             addr,
             port,
             miner,
+            threads,
+            pool,
+            share_difficulty,
             server,
             private_key_file,
         } => {
-            full_node(addr, port, miner, server, private_key_file);
+            let mining = match pool {
+                Some(port) => Mining::Pool {
+                    port,
+                    share_difficulty,
+                },
+                None if miner => Mining::Solo { threads },
+                None => Mining::Off,
+            };
+
+            full_node(addr, port, mining, server, private_key_file, params, config);
+        }
+        Command::MineWorker {
+            addr,
+            port,
+            worker,
+            payout_public_key,
+            threads,
+        } => {
+            mine_worker(addr, port, worker, payout_public_key, threads);
         }
         Command::Genesis {
             server,
             private_key_file,
+            spec,
+        } => {
+            genesis(server, private_key_file, spec, params, config);
+        }
+        Command::CreateGenesis {
+            file,
+            allocations,
+            message,
+            date,
+        } => {
+            create_genesis(file, allocations, message, date, params);
+        }
+        Command::Generate {
+            addr,
+            port,
+            count,
+            payee_public_key,
         } => {
-            genesis(server, private_key_file);
+            generate(addr, port, count, payee_public_key, params, config);
         }
         Command::Transaction {
             addr,
@@ -52,6 +99,8 @@ fn main() {
             payee_public_key,
             private_key_file,
             transaction_fee,
+            lock_time,
+            relative_lock,
         } => {
             transaction(
                 addr,
@@ -60,21 +109,181 @@ fn main() {
                 payee_public_key,
                 private_key_file,
                 transaction_fee,
+                lock_time,
+                relative_lock,
+                params,
+                config,
+            );
+        }
+        Command::PrepareTransaction {
+            addr,
+            port,
+            amount,
+            payee,
+            public_key_file,
+            transaction_file,
+            transaction_fee,
+            lock_time,
+            relative_lock,
+        } => {
+            prepare_transaction(
+                addr,
+                port,
+                amount,
+                payee,
+                public_key_file,
+                transaction_file,
+                transaction_fee,
+                lock_time,
+                relative_lock,
+                params,
+                config,
             );
         }
+        Command::Sign {
+            transaction_file,
+            private_key_file,
+            yes,
+        } => {
+            sign(transaction_file, private_key_file, yes);
+        }
+        Command::Broadcast {
+            addr,
+            port,
+            transaction_file,
+        } => {
+            broadcast(addr, port, transaction_file, params, config);
+        }
+        Command::CreateMultisig {
+            file,
+            required,
+            public_key_files,
+        } => {
+            create_multisig(file, required, public_key_files);
+        }
+        Command::MultisigBalance {
+            addr,
+            port,
+            multisig_file,
+        } => {
+            multisig_balance(addr, port, multisig_file, params, config);
+        }
+        Command::MultisigSpend {
+            addr,
+            port,
+            multisig_file,
+            amount,
+            payee,
+            spend_file,
+            transaction_fee,
+        } => {
+            multisig_spend(
+                addr,
+                port,
+                multisig_file,
+                amount,
+                payee,
+                spend_file,
+                transaction_fee,
+                params,
+                config,
+            );
+        }
+        Command::MultisigSign {
+            spend_file,
+            private_key_file,
+            yes,
+        } => {
+            multisig_sign(spend_file, private_key_file, yes);
+        }
+        Command::MultisigBroadcast {
+            addr,
+            port,
+            spend_file,
+        } => {
+            multisig_broadcast(addr, port, spend_file, params, config);
+        }
+        Command::CreateHtlc {
+            addr,
+            port,
+            amount,
+            recipient_public_key,
+            timeout,
+            private_key_file,
+            htlc_file,
+            hash,
+            transaction_fee,
+        } => {
+            create_htlc(
+                addr,
+                port,
+                amount,
+                recipient_public_key,
+                timeout,
+                private_key_file,
+                htlc_file,
+                hash,
+                transaction_fee,
+                params,
+                config,
+            );
+        }
+        Command::RedeemHtlc {
+            addr,
+            port,
+            htlc_file,
+            preimage,
+            private_key_file,
+            transaction_fee,
+        } => {
+            redeem_htlc(
+                addr,
+                port,
+                htlc_file,
+                preimage,
+                private_key_file,
+                transaction_fee,
+                params,
+                config,
+            );
+        }
+        Command::RefundHtlc {
+            addr,
+            port,
+            htlc_file,
+            private_key_file,
+            transaction_fee,
+        } => {
+            refund_htlc(
+                addr,
+                port,
+                htlc_file,
+                private_key_file,
+                transaction_fee,
+                params,
+                config,
+            );
+        }
+        Command::ExtractPreimage {
+            addr,
+            port,
+            htlc_file,
+        } => {
+            extract_preimage(addr, port, htlc_file, params, config);
+        }
         Command::Balance {
             addr,
             port,
             private_key_file,
         } => {
-            balance(addr, port, private_key_file);
+            balance(addr, port, private_key_file, params, config);
         }
         Command::Interactive {
             addr,
             port,
             private_key_file,
         } => {
-            interactive(addr, port, private_key_file);
+            interactive(addr, port, private_key_file, params, config);
         }
     }
 
@@ -1,43 +1,54 @@
 use std::{
-    io::{Result, Write},
-    net::TcpStream,
+    io::Result,
+    sync::{mpsc::Sender, Arc},
 };
 
-use super::{handle_stream, InternalMessage, Message, MessageType};
+use tokio::net::TcpStream;
 
-use bus::BusReader;
-use std::sync::mpsc::Sender;
+use super::{
+    handle_stream, write_message, Connection, Event, Message, MessageType, PeerRegistry,
+    TransportConfig,
+};
 
 pub struct Client {
-    client: Option<TcpStream>,
-    incoming_queue_sender: Sender<InternalMessage>,
-    outgoing_queue_receiver: Option<BusReader<InternalMessage>>,
+    client: Connection,
+    incoming_queue_sender: Sender<Event>,
+    peers: Arc<PeerRegistry>,
 }
 
 impl Client {
-    pub fn new(
+    pub async fn new(
         addr: String,
-        incoming_queue_sender: Sender<InternalMessage>,
-        outgoing_queue_receiver: BusReader<InternalMessage>,
+        transport: &TransportConfig,
+        incoming_queue_sender: Sender<Event>,
+        peers: Arc<PeerRegistry>,
     ) -> Result<Self> {
         Ok(Self {
-            client: Some(TcpStream::connect(addr)?),
+            client: transport
+                .establish(TcpStream::connect(addr).await?, true)
+                .await?,
             incoming_queue_sender,
-            outgoing_queue_receiver: Some(outgoing_queue_receiver),
+            peers,
         })
     }
 
-    pub fn start_networking(&mut self) {
-        let sender = self.incoming_queue_sender.clone();
-        let receiver = self.outgoing_queue_receiver.take().unwrap();
-
-        let mut stream = self.client.take().unwrap();
-        stream.set_nonblocking(true).unwrap();
-
-        stream
-            .write_all(&bincode::serialize(&Message::new(MessageType::Connect)).unwrap())
-            .unwrap();
-
-        handle_stream(stream, sender, receiver);
+    pub async fn start_networking(self) {
+        let mut connection = self.client;
+
+        let queue = self.peers.add(&connection.address);
+
+        if write_message(&mut connection.writer, &Message::new(MessageType::Connect))
+            .await
+            .is_ok()
+        {
+            handle_stream(
+                connection,
+                self.incoming_queue_sender,
+                queue,
+                self.peers,
+                None,
+            )
+            .await;
+        }
     }
 }
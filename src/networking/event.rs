@@ -0,0 +1,9 @@
+use super::InternalMessage;
+
+/// everything the middlewares get notified about
+pub enum Event {
+    Message(InternalMessage),
+    PeerConnected(String),
+    PeerDisconnected(String),
+    Shutdown,
+}
@@ -1,63 +1,114 @@
 use std::{
-    io::{Read, Write},
-    net::TcpStream,
-    sync::mpsc::Sender,
-    thread,
+    io::{Error, ErrorKind, Result},
+    sync::{mpsc::Sender, Arc},
 };
 
-use bus::BusReader;
-use log::info;
+use log::{info, warn};
+use tokio::sync::mpsc::Receiver;
 
-use crate::consts::BUFFER_SIZE;
+use crate::consts::MAX_MESSAGE_SIZE;
 
-use super::{InternalMessage, MessageDest, MessageSource};
+use super::{
+    Connection, ConnectionReader, ConnectionWriter, Event, InternalMessage, Message, MessageDest,
+    MessageSource, PeerRegistry, RateLimiter,
+};
+
+/// messages are framed as a 4 byte big endian length followed by the bincode encoded message
+pub async fn write_message(writer: &mut ConnectionWriter, message: &Message) -> Result<()> {
+    let bytes = bincode::serialize(message).unwrap();
+
+    let mut frame = (bytes.len() as u32).to_be_bytes().to_vec();
+    frame.extend_from_slice(&bytes);
+
+    writer.write_all(&frame).await
+}
+
+/// returns the message and its size in bytes
+pub async fn read_message(reader: &mut ConnectionReader) -> Result<(Message, usize)> {
+    let mut length = [0; 4];
+    reader.read_exact(&mut length).await?;
+
+    let length = u32::from_be_bytes(length) as usize;
+    if length > MAX_MESSAGE_SIZE {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("a message of {} bytes is too big", length),
+        ));
+    }
+
+    let mut buf = vec![0; length];
+    reader.read_exact(&mut buf).await?;
+
+    let message =
+        bincode::deserialize(&buf).map_err(|err| Error::new(ErrorKind::InvalidData, err))?;
+
+    Ok((message, length))
+}
 
 /// forward all messages
-/// - from stream to sender
-/// - from receiver to stream
-pub fn handle_stream(
-    mut stream: TcpStream,
-    sender: Sender<InternalMessage>,
-    mut receiver: BusReader<InternalMessage>,
+/// - from connection to sender
+/// - from the peer's queue to connection
+///
+/// until one of both directions fails or the queue is dropped
+///
+/// the sender also gets notified when the peer connects and disconnects
+///
+/// reading is throttled by the rate limiter, if there is one
+pub async fn handle_stream(
+    connection: Connection,
+    sender: Sender<Event>,
+    mut queue: Receiver<Message>,
+    peers: Arc<PeerRegistry>,
+    mut rate_limiter: Option<RateLimiter>,
 ) {
-    stream.set_nonblocking(false).unwrap();
-
-    let address = stream.peer_addr().unwrap().to_string();
-
-    let mut stream_clone = stream.try_clone().unwrap();
-    let address_clone = address.clone();
-
-    // sender thread
-    thread::spawn(move || {
-        while let Ok(msg) = receiver.recv() {
-            if msg.should_be_send_to(&address) {
-                if let Err(_) = stream.write_all(&bincode::serialize(&msg.message).unwrap()) {
-                    // connection shut down
-                    info!("The connection to {} was shut down", address);
-                    break;
-                }
-            }
+    let Connection {
+        address,
+        mut reader,
+        mut writer,
+    } = connection;
+
+    sender.send(Event::PeerConnected(address.clone())).unwrap();
+
+    let sending = async {
+        while let Some(message) = queue.recv().await {
+            write_message(&mut writer, &message).await?;
         }
-    });
 
-    // receiver thread
-    thread::spawn(move || {
-        let mut buf = [0; BUFFER_SIZE];
+        Ok(())
+    };
 
-        while let Ok(bytes) = stream_clone.read(&mut buf) {
-            if bytes == 0 {
-                // connection shut down
-                info!("The connection to {} was shut down", address_clone);
-                break;
+    let receiving = async {
+        loop {
+            let (message, size) = read_message(&mut reader).await?;
+
+            if let Some(rate_limiter) = &mut rate_limiter {
+                rate_limiter.throttle(size).await;
             }
 
-            let message = InternalMessage::from_message(
-                bincode::deserialize(&buf[0..bytes]).unwrap(),
-                MessageSource::Foreign(address_clone.clone()),
-                MessageDest::Localhost,
-            );
+            sender
+                .send(Event::Message(InternalMessage::from_message(
+                    message,
+                    MessageSource::Foreign(address.clone()),
+                    MessageDest::Localhost,
+                )))
+                .unwrap();
+        }
+    };
+
+    // whichever direction finishes first cancels the other one
+    let result: Result<()> = tokio::select! {
+        result = sending => result,
+        result = receiving => result,
+    };
 
-            sender.send(message).unwrap();
+    match result {
+        Err(err) if err.kind() != ErrorKind::UnexpectedEof => {
+            warn!("Dropping the connection to {}: {}", address, err)
         }
-    });
+        // connection shut down
+        _ => info!("The connection to {} was shut down", address),
+    }
+
+    peers.remove(&address);
+    sender.send(Event::PeerDisconnected(address)).unwrap();
 }
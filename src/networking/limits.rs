@@ -0,0 +1,134 @@
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use tokio::time::sleep;
+
+/// limits protecting a node against peers which open too many connections or send too much
+#[derive(Clone)]
+pub struct Limits {
+    pub max_inbound_connections: usize,
+    pub max_connections_per_ip: usize,
+    pub max_messages_per_second: u32,
+    pub max_bytes_per_second: u64,
+    pub max_concurrent_syncs: usize,
+}
+
+/// counts the open inbound connections in total and per ip
+pub struct ConnectionLimiter {
+    max_total: usize,
+    max_per_ip: usize,
+    connections: Arc<Mutex<HashMap<IpAddr, usize>>>,
+}
+
+impl ConnectionLimiter {
+    pub fn new(limits: &Limits) -> Self {
+        Self {
+            max_total: limits.max_inbound_connections,
+            max_per_ip: limits.max_connections_per_ip,
+            connections: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// the connection counts as open as long as the slot lives
+    pub fn try_acquire(&self, ip: IpAddr) -> Result<ConnectionSlot, String> {
+        let mut connections = self.connections.lock().unwrap();
+
+        if connections.values().sum::<usize>() >= self.max_total {
+            return Err(format!(
+                "there are already {} inbound connections",
+                self.max_total
+            ));
+        }
+
+        let count = connections.entry(ip).or_insert(0);
+        if *count >= self.max_per_ip {
+            return Err(format!(
+                "there are already {} connections from {}",
+                self.max_per_ip, ip
+            ));
+        }
+        *count += 1;
+
+        Ok(ConnectionSlot {
+            ip,
+            connections: self.connections.clone(),
+        })
+    }
+}
+
+pub struct ConnectionSlot {
+    ip: IpAddr,
+    connections: Arc<Mutex<HashMap<IpAddr, usize>>>,
+}
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        let mut connections = self.connections.lock().unwrap();
+
+        if let Some(count) = connections.get_mut(&self.ip) {
+            *count -= 1;
+            if *count == 0 {
+                connections.remove(&self.ip);
+            }
+        }
+    }
+}
+
+struct TokenBucket {
+    rate: f64,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: f64) -> Self {
+        Self {
+            rate,
+            tokens: rate,
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// take the tokens and return how long to wait until the bucket isn't in debt anymore
+    fn take(&mut self, amount: f64) -> Duration {
+        let now = Instant::now();
+        self.tokens =
+            (self.tokens + (now - self.last_refill).as_secs_f64() * self.rate).min(self.rate);
+        self.last_refill = now;
+
+        self.tokens -= amount;
+
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.rate)
+        }
+    }
+}
+
+/// throttles reading from a peer which sends too many messages or bytes per second
+pub struct RateLimiter {
+    messages: TokenBucket,
+    bytes: TokenBucket,
+}
+
+impl RateLimiter {
+    pub fn new(limits: &Limits) -> Self {
+        Self {
+            messages: TokenBucket::new(limits.max_messages_per_second as f64),
+            bytes: TokenBucket::new(limits.max_bytes_per_second as f64),
+        }
+    }
+
+    pub async fn throttle(&mut self, bytes: usize) {
+        let wait = self.messages.take(1.0).max(self.bytes.take(bytes as f64));
+
+        if !wait.is_zero() {
+            sleep(wait).await;
+        }
+    }
+}
@@ -13,6 +13,10 @@ pub enum MessageType {
     SendBlockchainTransaction(Transaction),
     Transaction(Transaction),
     MinedBlock(Block),
+    /// the hash of a block or transaction the peer sent us and why we rejected it
+    Rejected(Vec<u8>, String),
+    /// asks a peer for a block we don't know, it answers with a MinedBlock
+    GetBlock(Vec<u8>),
 }
 
 impl MessageType {
@@ -24,6 +28,8 @@ impl MessageType {
             MessageType::MinedBlock(_) => "MinedBlock",
             MessageType::SendBlockchainBlock(_) => "SendBlockchainBlock",
             MessageType::SendBlockchainTransaction(_) => "SendBlockchainTransaction",
+            MessageType::Rejected(_, _) => "Rejected",
+            MessageType::GetBlock(_) => "GetBlock",
         }
         .to_string()
     }
@@ -54,7 +60,7 @@ impl MessageSource {
     pub fn to_string(&self) -> String {
         match self {
             MessageSource::Localhost => "Localhost",
-            MessageSource::Foreign(_) => "Foreign",
+            MessageSource::Foreign(addr) => addr,
         }
         .to_string()
     }
@@ -72,7 +78,6 @@ impl MessageSource {
 pub enum MessageDest {
     Localhost,
     Broadcast,
-    Single(String),
 }
 
 impl MessageDest {
@@ -80,7 +85,6 @@ impl MessageDest {
         match self {
             MessageDest::Localhost => "Localhost",
             MessageDest::Broadcast => "Broadcast",
-            MessageDest::Single(_) => "Single",
         }
         .to_string()
     }
@@ -114,13 +118,6 @@ impl InternalMessage {
             }
         }
 
-        // check destination if it should only be send to one address
-        if let MessageDest::Single(addr) = &self.dest {
-            if addr != address {
-                return false;
-            }
-        }
-
         true
     }
 }
@@ -1,35 +1,83 @@
-use std::sync::{Arc, Mutex};
+use std::sync::{mpsc::Sender, Arc};
 
-use bus::Bus;
 use log::warn;
-use std::sync::mpsc::Sender;
 
 use crate::{
     blockchain::Blockchain,
-    networking::{InternalMessage, MessageType},
+    networking::{Event, InternalMessage, MessageType, OrphanPool, PeerRegistry},
+    util::to_hex,
 };
 
-use super::middleware::Middleware;
+use super::{Middleware, MiddlewareResult};
 
-pub struct GenesisMiddleware;
+#[derive(Default)]
+pub struct GenesisMiddleware {
+    orphans: OrphanPool,
+}
 
 impl Middleware for GenesisMiddleware {
+    fn name(&self) -> &'static str {
+        "genesis"
+    }
+
     fn on_message(
         &mut self,
         message: &InternalMessage,
-        _preprocessing_sender: &Sender<InternalMessage>,
-        _postprocessing_sender: Arc<Mutex<Bus<InternalMessage>>>,
+        preprocessing_sender: &Sender<Event>,
+        postprocessing_sender: Arc<PeerRegistry>,
         chain: &mut Blockchain,
-    ) {
+    ) -> Result<MiddlewareResult, String> {
         match &message.message.message_type {
             MessageType::Connect => {}
             MessageType::SendBlockchain(_, _) => {
                 warn!("Someone sent the root node a blockchain");
             }
-            MessageType::Transaction(_) => {}
+            MessageType::Transaction(transaction) => {
+                // don't handle and forward a transaction twice
+                if chain.contains_unmined_transaction(&transaction.hash()) {
+                    return Ok(MiddlewareResult::Halt);
+                }
+
+                if let Err(err) = chain.push_unmined_transaction(transaction.clone()) {
+                    postprocessing_sender.reject(
+                        &message.source,
+                        transaction.hash(),
+                        &err.to_string(),
+                        err.ban_score(),
+                    );
+                    return Err(format!(
+                        "rejected the transaction {} from {}: {}",
+                        to_hex(&transaction.hash()),
+                        message.source.to_string(),
+                        err
+                    ));
+                }
+            }
             MessageType::MinedBlock(block) => {
-                if !chain.push_block(block.clone()) {
-                    warn!("Someone sent a wrong block");
+                match self.orphans.push_block(
+                    block,
+                    &message.source,
+                    chain,
+                    preprocessing_sender,
+                    &postprocessing_sender,
+                ) {
+                    Ok(true) => {}
+                    // don't handle and forward a block twice, orphans are handled once they are connected
+                    Ok(false) => return Ok(MiddlewareResult::Halt),
+                    Err(err) => {
+                        postprocessing_sender.reject(
+                            &message.source,
+                            block.hash(),
+                            &err.to_string(),
+                            err.ban_score(),
+                        );
+                        return Err(format!(
+                            "rejected the block {} from {}: {}",
+                            to_hex(&block.hash()),
+                            message.source.to_string(),
+                            err
+                        ));
+                    }
                 }
             }
             MessageType::SendBlockchainBlock(_) => {
@@ -38,6 +86,28 @@ impl Middleware for GenesisMiddleware {
             MessageType::SendBlockchainTransaction(_) => {
                 warn!("Someone sent the root node a blockchain transaction");
             }
+            MessageType::Rejected(hash, reason) => {
+                warn!(
+                    "{} rejected {}: {}",
+                    message.source.to_string(),
+                    to_hex(hash),
+                    reason
+                );
+
+                // it's only meant for us
+                return Ok(MiddlewareResult::Halt);
+            }
+            MessageType::GetBlock(hash) => {
+                if let Some(block) = chain.find_block(hash) {
+                    postprocessing_sender
+                        .send_to(&message.source.unwrap(), MessageType::MinedBlock(block));
+                }
+
+                // it's only meant for us
+                return Ok(MiddlewareResult::Halt);
+            }
         }
+
+        Ok(MiddlewareResult::Continue)
     }
 }
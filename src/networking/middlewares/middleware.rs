@@ -1,16 +1,148 @@
-use std::sync::{Arc, Mutex};
+use std::sync::{mpsc::Sender, Arc};
 
-use bus::Bus;
-use std::sync::mpsc::Sender;
+use crate::{
+    blockchain::Blockchain,
+    networking::{Event, InternalMessage, PeerRegistry},
+};
 
-use crate::{blockchain::Blockchain, networking::InternalMessage};
+/// what happens with a message after a middleware handled it
+pub enum MiddlewareResult {
+    /// pass the message on to the next middleware
+    Continue,
+    /// don't run the following middlewares for this message
+    Halt,
+    /// run the following middlewares with this message instead
+    Replace(InternalMessage),
+}
 
+/// middlewares run in an order which satisfies all `run_after` and `run_before` constraints
+/// and otherwise prefers a higher priority
+///
+/// an error is logged with the name of the middleware and halts the message
 pub trait Middleware {
+    /// the name other middlewares use in their ordering constraints
+    fn name(&self) -> &'static str;
+
+    fn priority(&self) -> i32 {
+        0
+    }
+
+    /// the middlewares which have to run before this one, if they exist
+    fn run_after(&self) -> Vec<&'static str> {
+        vec![]
+    }
+
+    /// the middlewares which have to run after this one, if they exist
+    fn run_before(&self) -> Vec<&'static str> {
+        vec![]
+    }
+
+    fn on_start(
+        &mut self,
+        _preprocessing_sender: &Sender<Event>,
+        _postprocessing_sender: Arc<PeerRegistry>,
+        _chain: &mut Blockchain,
+    ) -> Result<(), String> {
+        Ok(())
+    }
+
     fn on_message(
         &mut self,
         message: &InternalMessage,
-        preprocessing_sender: &Sender<InternalMessage>,
-        postprocessing_sender: Arc<Mutex<Bus<InternalMessage>>>,
+        preprocessing_sender: &Sender<Event>,
+        postprocessing_sender: Arc<PeerRegistry>,
         chain: &mut Blockchain,
-    );
+    ) -> Result<MiddlewareResult, String>;
+
+    fn on_peer_connected(
+        &mut self,
+        _address: &str,
+        _preprocessing_sender: &Sender<Event>,
+        _postprocessing_sender: Arc<PeerRegistry>,
+        _chain: &mut Blockchain,
+    ) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn on_peer_disconnected(
+        &mut self,
+        _address: &str,
+        _preprocessing_sender: &Sender<Event>,
+        _postprocessing_sender: Arc<PeerRegistry>,
+        _chain: &mut Blockchain,
+    ) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn on_shutdown(&mut self, _chain: &mut Blockchain) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// sort the middlewares topologically by their ordering constraints,
+/// picking the one with the highest priority (and then the one added first) whenever there is a choice
+pub fn order_middlewares(
+    middlewares: Vec<Box<dyn Middleware>>,
+) -> Result<Vec<Box<dyn Middleware>>, String> {
+    let names: Vec<_> = middlewares
+        .iter()
+        .map(|middleware| middleware.name())
+        .collect();
+
+    // predecessors[i] contains all middlewares which have to run before middleware i
+    let mut predecessors: Vec<Vec<usize>> = vec![vec![]; middlewares.len()];
+
+    for (i, middleware) in middlewares.iter().enumerate() {
+        for name in middleware.run_after() {
+            for (j, _) in names
+                .iter()
+                .enumerate()
+                .filter(|(_, other)| **other == name)
+            {
+                predecessors[i].push(j);
+            }
+        }
+
+        for name in middleware.run_before() {
+            for (j, _) in names
+                .iter()
+                .enumerate()
+                .filter(|(_, other)| **other == name)
+            {
+                predecessors[j].push(i);
+            }
+        }
+    }
+
+    let mut order: Vec<usize> = vec![];
+
+    while order.len() < middlewares.len() {
+        let next = (0..middlewares.len())
+            .filter(|i| !order.contains(i))
+            .filter(|i| predecessors[*i].iter().all(|j| order.contains(j)))
+            // max_by_key returns the last maximum, so reverse to prefer the first one added
+            .rev()
+            .max_by_key(|i| middlewares[*i].priority());
+
+        match next {
+            Some(i) => order.push(i),
+            None => {
+                return Err(format!(
+                    "the ordering constraints of {} are circular",
+                    (0..middlewares.len())
+                        .filter(|i| !order.contains(i))
+                        .map(|i| names[i])
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ))
+            }
+        }
+    }
+
+    let mut middlewares: Vec<_> = middlewares.into_iter().map(Some).collect();
+
+    Ok(order
+        .into_iter()
+        .map(|i| middlewares[i].take().unwrap())
+        .collect())
 }
@@ -1,63 +1,207 @@
 use std::{
-    sync::mpsc::{channel, Sender},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc::Sender,
+        Arc,
+    },
     thread,
+    time::Instant,
 };
 
-use log::info;
+use log::{debug, info};
+use rand::random;
 
 use crate::{
     blockchain::Block,
+    consts::{HASHRATE_LOG_INTERVAL, KILLSWITCH_POLL_INTERVAL, TIMESTAMP_ROLL_INTERVAL},
     networking::{
         message::{MessageDest, MessageSource},
-        InternalMessage, MessageType,
+        Event, InternalMessage, MessageType,
     },
+    util::time_since_unix_epoch,
 };
 
+// how many hashes a thread tries before adding them to the stats
+const HASH_BATCH_SIZE: u64 = 1024;
+
+/// mining statistics, shared between the miner threads and everyone interested
+#[derive(Default)]
+pub struct MiningStats {
+    hashes: AtomicU64,
+    blocks_found: AtomicU64,
+    hashrate: AtomicU64,
+}
+
+impl MiningStats {
+    /// all hashes tried since the miner was created
+    pub fn hashes(&self) -> u64 {
+        self.hashes.load(Ordering::Relaxed)
+    }
+
+    pub fn blocks_found(&self) -> u64 {
+        self.blocks_found.load(Ordering::Relaxed)
+    }
+
+    /// hashes per second, measured over the last log interval
+    pub fn hashrate(&self) -> u64 {
+        self.hashrate.load(Ordering::Relaxed)
+    }
+}
+
 pub struct Miner {
-    killswitch_sender: Option<Sender<()>>,
+    threads: usize,
+    killswitch: Option<Arc<AtomicBool>>,
+    stats: Arc<MiningStats>,
 }
 
 impl Miner {
-    pub fn new() -> Self {
+    pub fn new(threads: usize) -> Self {
         Self {
-            killswitch_sender: None,
+            threads: threads.max(1),
+            killswitch: None,
+            stats: Arc::new(MiningStats::default()),
         }
     }
 
-    pub fn mine(&mut self, mut block: Block, result_sender: Sender<InternalMessage>) {
-        info!("Started mining");
-        let (killswitch_sender, killswitch_receiver) = channel();
-        self.killswitch_sender = Some(killswitch_sender);
+    pub fn stats(&self) -> Arc<MiningStats> {
+        self.stats.clone()
+    }
 
-        thread::spawn(move || loop {
-            if killswitch_receiver.try_recv().is_ok() {
-                break;
-            }
+    /// mine with all threads until the block's hash starts with difficulty null bytes,
+    /// thread i tries the nonces i, i + threads, i + 2 * threads, ...
+    /// (starting near the block's nonce)
+    ///
+    /// when a thread runs out of nonces, it continues with the next extra nonce,
+    /// which doesn't overlap with the other threads because they still try other nonces
+    pub fn mine(&mut self, mut block: Block, difficulty: usize, result_sender: Sender<Event>) {
+        info!("Started mining with {} threads", self.threads);
+
+        let killswitch = Arc::new(AtomicBool::new(false));
+        self.killswitch = Some(killswitch.clone());
+
+        // hashes tried on this block
+        let block_hashes = Arc::new(AtomicU64::new(0));
+
+        // a random extra nonce keeps our coinbase different from the ones in earlier blocks
+        let mut extra_nonce: u64 = random();
+        block.set_extra_nonce(extra_nonce);
+
+        let step = self.threads as u64;
+        let first_nonce = block.nonce - block.nonce % step;
+
+        for i in 0..step {
+            let mut block = block.clone();
+            // start over if the nonce would overflow, i still belongs to this thread
+            block.nonce = first_nonce.checked_add(i).unwrap_or(i);
+
+            let killswitch = killswitch.clone();
+            let block_hashes = block_hashes.clone();
+            let stats = self.stats.clone();
+            let result_sender = result_sender.clone();
 
-            if block.verify_nonce() {
-                info!("Solved a block: {}", block.nonce);
-                result_sender
-                    .send(InternalMessage::new(
-                        MessageType::MinedBlock(block),
-                        MessageSource::Localhost,
-                        MessageDest::Localhost,
-                    ))
-                    .unwrap();
-                break;
-            } else {
-                block.nonce += 1;
+            thread::spawn(move || {
+                let mut hashes = 0;
+                let mut last_roll = Instant::now();
+
+                // only hash the header, the transactions hash only changes with the extra nonce
+                let mut header = block.header();
+
+                while !killswitch.load(Ordering::Relaxed) {
+                    if header.meets_difficulty(difficulty) {
+                        // only the first thread which finds a solution sends it
+                        if !killswitch.swap(true, Ordering::Relaxed) {
+                            block.nonce = header.nonce;
+                            block.date = header.date;
+
+                            info!("Solved a block: {}", block.nonce);
+                            stats.blocks_found.fetch_add(1, Ordering::Relaxed);
+
+                            result_sender
+                                .send(Event::Message(InternalMessage::new(
+                                    MessageType::MinedBlock(block),
+                                    MessageSource::Localhost,
+                                    MessageDest::Localhost,
+                                )))
+                                .unwrap();
+                        }
+                        break;
+                    }
+
+                    header.nonce = match header.nonce.checked_add(step) {
+                        Some(nonce) => nonce,
+                        None => {
+                            extra_nonce = extra_nonce.wrapping_add(1);
+                            block.set_extra_nonce(extra_nonce);
+                            header.transactions_hash = block.header().transactions_hash;
+                            debug!(
+                                "Nonces exhausted, continuing with extra nonce {}",
+                                extra_nonce
+                            );
+
+                            i
+                        }
+                    };
+
+                    hashes += 1;
+                    if hashes == HASH_BATCH_SIZE {
+                        block_hashes.fetch_add(hashes, Ordering::Relaxed);
+                        stats.hashes.fetch_add(hashes, Ordering::Relaxed);
+                        hashes = 0;
+
+                        // don't mine with a stale timestamp
+                        // never before the template's date, it may be ahead of our clock
+                        if last_roll.elapsed() >= TIMESTAMP_ROLL_INTERVAL {
+                            header.date = header.date.max(time_since_unix_epoch());
+                            last_roll = Instant::now();
+                        }
+                    }
+                }
+            });
+        }
+
+        let stats = self.stats.clone();
+
+        thread::spawn(move || {
+            // a hash matches with a chance of 1 / 256 per needed null byte
+            let expected_hashes = 256_f64.powi(difficulty as i32);
+
+            let mut last_hashes = 0;
+            let mut last_time = Instant::now();
+
+            loop {
+                // not the whole log interval, the thread would outlive the miner for that long
+                thread::sleep(KILLSWITCH_POLL_INTERVAL);
+
+                if killswitch.load(Ordering::Relaxed) {
+                    break;
+                }
+                if last_time.elapsed() < HASHRATE_LOG_INTERVAL {
+                    continue;
+                }
+
+                let hashes = block_hashes.load(Ordering::Relaxed);
+                let hashrate = (hashes - last_hashes) as f64 / last_time.elapsed().as_secs_f64();
+                stats.hashrate.store(hashrate as u64, Ordering::Relaxed);
+
+                info!(
+                    "Mining at {:.0} H/s, tried {} hashes ({:.0}% of the expected {:.0})",
+                    hashrate,
+                    hashes,
+                    hashes as f64 / expected_hashes * 100.0,
+                    expected_hashes
+                );
+
+                last_hashes = hashes;
+                last_time = Instant::now();
             }
         });
     }
 
     pub fn abort(&mut self) {
-        if let Some(sender) = &self.killswitch_sender {
-            match sender.send(()) {
-                Ok(_) => info!("Killing miner"),
-                Err(_) => {}
-            };
-
-            self.killswitch_sender = None;
+        if let Some(killswitch) = self.killswitch.take() {
+            if !killswitch.swap(true, Ordering::Relaxed) {
+                info!("Killing miner");
+            }
         }
     }
 }
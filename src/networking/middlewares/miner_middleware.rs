@@ -1,91 +1,88 @@
-use std::sync::{Arc, Mutex};
-
-use bus::Bus;
-use log::warn;
-use std::sync::mpsc::Sender;
+use std::sync::{mpsc::Sender, Arc};
 
 use crate::{
-    blockchain::{Block, Blockchain, Transaction, TransactionOutput, Wallet},
-    consts::MINING_REWARD,
-    networking::{InternalMessage, MessageType},
+    blockchain::{BlockTemplateBuilder, Blockchain, Wallet},
+    networking::{Event, InternalMessage, MessageType, PeerRegistry},
 };
 
-use super::{middleware::Middleware, Miner};
+use super::{miner::MiningStats, Middleware, MiddlewareResult, Miner};
 
 pub struct MinerMiddleware {
-    transactions: Vec<Transaction>,
+    template_builder: BlockTemplateBuilder,
     miner: Miner,
-    wallet: Wallet,
+    // the block the miner currently mines on
+    tip: Option<Vec<u8>>,
 }
 
 impl MinerMiddleware {
-    pub fn new(wallet: Wallet) -> Self {
+    pub fn new(wallet: Wallet, threads: usize) -> Self {
         Self {
-            transactions: vec![],
-            miner: Miner::new(),
-            wallet,
+            template_builder: BlockTemplateBuilder::new(wallet.public_key),
+            miner: Miner::new(threads),
+            tip: None,
+        }
+    }
+
+    pub fn stats(&self) -> Arc<MiningStats> {
+        self.miner.stats()
+    }
+
+    /// start mining a new block template, even if there are no transactions
+    fn restart(&mut self, preprocessing_sender: &Sender<Event>, chain: &mut Blockchain) {
+        self.miner.abort();
+
+        self.tip = chain.main_chain().last().map(|block| block.hash());
+
+        if let Some(block) = self.template_builder.build(chain) {
+            self.miner
+                .mine(block, chain.params.difficulty, preprocessing_sender.clone());
         }
     }
 }
 
 impl Middleware for MinerMiddleware {
+    fn name(&self) -> &'static str {
+        "miner"
+    }
+
+    // mined blocks and transactions have to be in the chain before mining on top of them
+    fn run_after(&self) -> Vec<&'static str> {
+        vec!["node", "genesis"]
+    }
+
+    fn on_start(
+        &mut self,
+        preprocessing_sender: &Sender<Event>,
+        _postprocessing_sender: Arc<PeerRegistry>,
+        chain: &mut Blockchain,
+    ) -> Result<(), String> {
+        self.restart(preprocessing_sender, chain);
+        Ok(())
+    }
+
     fn on_message(
         &mut self,
         message: &InternalMessage,
-        preprocessing_sender: &Sender<InternalMessage>,
-        _postprocessing_sender: Arc<Mutex<Bus<InternalMessage>>>,
+        preprocessing_sender: &Sender<Event>,
+        _postprocessing_sender: Arc<PeerRegistry>,
         chain: &mut Blockchain,
-    ) {
-        if let MessageType::Transaction(transaction) = &message.message.message_type {
-            if !transaction.verify(chain) {
-                warn!("Received a wrong transaction");
-                return;
-            }
-
-            self.transactions.push(transaction.clone());
-            self.miner.abort();
-
-            let mut new_block = Block::new(
-                chain.main_chain().last().unwrap().hash(),
-                self.transactions.clone(),
-            );
-
-            // add the transaction where the miner gets money
-            new_block.transactions.push(
-                Transaction::new(
-                    MINING_REWARD,
-                    0,
-                    None,
-                    self.wallet.public_key.clone(),
-                    chain,
-                )
-                .unwrap(),
-            );
-
-            for transaction in &mut self.transactions {
-                let tx_ins_sum = transaction.tx_ins_sum(chain).unwrap();
-                let tx_outs_sum = transaction.tx_outs_sum();
-
-                // if there is one, get transaction fee
-                if tx_ins_sum > tx_outs_sum {
-                    transaction.transaction_outputs.push(TransactionOutput::new(
-                        tx_ins_sum - tx_outs_sum,
-                        self.wallet.public_key.clone(),
-                    ));
-                }
-            }
-
-            self.miner.mine(new_block, preprocessing_sender.clone());
+    ) -> Result<MiddlewareResult, String> {
+        // the template would change with every block of the chain
+        if chain.is_receiving() {
+            return Ok(MiddlewareResult::Continue);
         }
 
-        if let MessageType::MinedBlock(block) = &message.message.message_type {
-            if !chain.push_block(block.clone()) {
-                warn!("Received a wrong block");
-                return;
-            }
+        let tip_changed = chain.main_chain().last().map(|block| block.hash()) != self.tip;
 
-            self.transactions.clear();
-            self.miner.abort();
+        if tip_changed || matches!(message.message.message_type, MessageType::Transaction(_)) {
+            self.restart(preprocessing_sender, chain);
         }
+
+        Ok(MiddlewareResult::Continue)
+    }
+
+    fn on_shutdown(&mut self, _chain: &mut Blockchain) -> Result<(), String> {
+        self.miner.abort();
+        Ok(())
     }
 }
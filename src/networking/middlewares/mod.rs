@@ -3,11 +3,15 @@ mod middleware;
 mod miner;
 mod miner_middleware;
 mod node_middleware;
+mod pool_middleware;
 mod server_middleware;
+mod time_middleware;
 
 pub use genesis_middleware::GenesisMiddleware;
-pub use middleware::Middleware;
+pub use middleware::{order_middlewares, Middleware, MiddlewareResult};
 pub use miner::Miner;
 pub use miner_middleware::MinerMiddleware;
 pub use node_middleware::NodeMiddleware;
+pub use pool_middleware::PoolMiddleware;
 pub use server_middleware::ServerMiddleware;
+pub use time_middleware::TimeMiddleware;
@@ -1,59 +1,78 @@
 use std::{
     process::exit,
-    sync::{Arc, Mutex},
+    sync::{mpsc::Sender, Arc},
 };
 
-use bus::Bus;
-use log::{error, info, warn};
-use std::sync::mpsc::Sender;
+use log::{debug, error, info, warn};
 
 use crate::{
     blockchain::Blockchain,
-    networking::{InternalMessage, MessageSource, MessageType},
+    networking::{Event, InternalMessage, MessageType, OrphanPool, PeerRegistry},
+    util::to_hex,
 };
 
-use super::middleware::Middleware;
+use super::{Middleware, MiddlewareResult};
+
+type OnChainReceived = dyn FnMut(&Sender<Event>, Arc<PeerRegistry>, &mut Blockchain);
 
 pub struct NodeMiddleware {
     is_server: bool,
-    is_miner: bool,
-    on_chain_received:
-        Box<dyn FnMut(&Sender<InternalMessage>, Arc<Mutex<Bus<InternalMessage>>>, &mut Blockchain)>,
+    on_chain_received: Box<OnChainReceived>,
     block_index: usize,
     transaction_index: usize,
     num_blocks_in_chain: usize,
     num_unmined_transactions_in_chain: usize,
+    orphans: OrphanPool,
 }
 impl NodeMiddleware {
     pub fn new(
         is_server: bool,
-        is_miner: bool,
-        on_chain_received: impl FnMut(&Sender<InternalMessage>, Arc<Mutex<Bus<InternalMessage>>>, &mut Blockchain)
-            + 'static,
+        on_chain_received: impl FnMut(&Sender<Event>, Arc<PeerRegistry>, &mut Blockchain) + 'static,
     ) -> Self {
         Self {
             is_server,
-            is_miner,
             block_index: 0,
             transaction_index: 0,
             num_blocks_in_chain: 0,
             num_unmined_transactions_in_chain: 0,
+            orphans: OrphanPool::default(),
             on_chain_received: Box::new(on_chain_received),
         }
     }
+
+    fn receiving_chain(&self, chain: &Blockchain) -> bool {
+        chain.chain.is_none() || self.block_index < self.num_blocks_in_chain
+    }
 }
 
 impl Middleware for NodeMiddleware {
+    fn name(&self) -> &'static str {
+        "node"
+    }
+
     fn on_message(
         &mut self,
         message: &InternalMessage,
-        preprocessing_sender: &Sender<InternalMessage>,
-        postprocessing_sender: Arc<Mutex<Bus<InternalMessage>>>,
+        preprocessing_sender: &Sender<Event>,
+        postprocessing_sender: Arc<PeerRegistry>,
         chain: &mut Blockchain,
-    ) {
+    ) -> Result<MiddlewareResult, String> {
+        // blocks and transactions can't be checked before the whole chain arrived
+        if let MessageType::MinedBlock(_) | MessageType::Transaction(_) =
+            message.message.message_type
+        {
+            if self.receiving_chain(chain) {
+                debug!(
+                    "Ignoring a {} message while receiving the chain",
+                    message.message.message_type.to_string()
+                );
+                return Ok(MiddlewareResult::Halt);
+            }
+        }
+
         match &message.message.message_type {
-            &MessageType::MinedBlock(_) => {}
-            &MessageType::SendBlockchainBlock(_) => {}
+            MessageType::MinedBlock(_) => {}
+            MessageType::SendBlockchainBlock(_) => {}
             _ => {
                 if self.block_index < self.num_blocks_in_chain
                     || self.transaction_index < self.num_unmined_transactions_in_chain
@@ -74,10 +93,36 @@ impl Middleware for NodeMiddleware {
                 self.num_unmined_transactions_in_chain = *num_unmined_transactions;
 
                 info!("Receiving chain...");
+                // local test networks don't need to pin it
+                if chain.chain.is_none()
+                    && chain.params.genesis_hash.is_none()
+                    && !chain.params.generate_on_demand
+                {
+                    warn!(
+                        "The {} network doesn't pin its genesis block, trusting the server's. Pin it with genesis_hash in a chain parameters file",
+                        chain.params.name
+                    );
+                }
+                chain.start_sync();
             }
             MessageType::SendBlockchainBlock(block) => {
-                if !chain.push_block(block.clone()) {
-                    warn!("Got a wrong block from the server");
+                if let Err(err) = chain.push_block(block.clone()) {
+                    if chain.chain.is_none() {
+                        error!("The server's chain is wrong: {}", err);
+                        exit(1);
+                    }
+
+                    warn!(
+                        "Got a wrong block {} from the server: {}",
+                        to_hex(&block.hash()),
+                        err
+                    );
+                    postprocessing_sender.reject(
+                        &message.source,
+                        block.hash(),
+                        &err.to_string(),
+                        err.ban_score(),
+                    );
                 }
 
                 info!(
@@ -92,20 +137,29 @@ impl Middleware for NodeMiddleware {
                     info!("Done receiving chain");
 
                     info!("Verifying chain...");
-                    if chain.verify() {
-                        info!("Chain is correct");
-                        chain.compute_utxos();
-                    } else {
-                        error!("Chain is wrong!");
+                    chain.finish_sync();
+                    if let Err(err) = chain.verify() {
+                        error!("Chain is wrong: {}", err);
                         info!("{:#?}", chain);
                         exit(1);
                     }
+                    info!("Chain is correct");
+                    chain.compute_utxos();
 
                     self.block_index = 0;
                     self.num_blocks_in_chain = 0;
 
-                    // weird syntax to run the closure
-                    (self.on_chain_received)(preprocessing_sender, postprocessing_sender, chain);
+                    // the unmined transactions come after the blocks
+                    if self.num_unmined_transactions_in_chain == 0 {
+                        chain.finish_receiving();
+
+                        // weird syntax to run the closure
+                        (self.on_chain_received)(
+                            preprocessing_sender,
+                            postprocessing_sender,
+                            chain,
+                        );
+                    }
                 }
             }
             MessageType::SendBlockchainTransaction(transaction) => {
@@ -121,24 +175,113 @@ impl Middleware for NodeMiddleware {
 
                 if self.transaction_index == self.num_unmined_transactions_in_chain {
                     info!("Done receiving unmined transactions");
+                    chain.finish_receiving();
+
+                    (self.on_chain_received)(preprocessing_sender, postprocessing_sender, chain);
+                }
+            }
+            MessageType::Transaction(transaction) => {
+                // don't handle and forward a transaction twice
+                if chain.contains_unmined_transaction(&transaction.hash()) {
+                    return Ok(MiddlewareResult::Halt);
+                }
+
+                if let Err(err) = chain.push_unmined_transaction(transaction.clone()) {
+                    postprocessing_sender.reject(
+                        &message.source,
+                        transaction.hash(),
+                        &err.to_string(),
+                        err.ban_score(),
+                    );
+                    return Err(format!(
+                        "rejected the transaction {} from {}: {}",
+                        to_hex(&transaction.hash()),
+                        message.source.to_string(),
+                        err
+                    ));
                 }
             }
-            MessageType::Transaction(_) => {}
             MessageType::MinedBlock(block) => {
-                if !self.is_miner || message.source == MessageSource::Localhost {
-                    if !chain.push_block(block.clone()) {
-                        warn!("Received a wrong mined block");
-                        return;
+                // a mined block is sent alone, so don't take over children nobody verified
+                let mut message = message.clone();
+                let mut block = block.clone();
+                let had_children = !block.children.is_empty();
+                block.children = vec![];
+                message.message.message_type = MessageType::MinedBlock(block.clone());
+
+                match self.orphans.push_block(
+                    &block,
+                    &message.source,
+                    chain,
+                    preprocessing_sender,
+                    &postprocessing_sender,
+                ) {
+                    Ok(true) => {}
+                    // don't handle and forward a block twice, orphans are handled once they are connected
+                    Ok(false) => return Ok(MiddlewareResult::Halt),
+                    Err(err) => {
+                        postprocessing_sender.reject(
+                            &message.source,
+                            block.hash(),
+                            &err.to_string(),
+                            err.ban_score(),
+                        );
+                        return Err(format!(
+                            "rejected the block {} from {}: {}",
+                            to_hex(&block.hash()),
+                            message.source.to_string(),
+                            err
+                        ));
                     }
                 }
 
                 if !self.is_server {
+                    postprocessing_sender.send(message.clone());
+                }
+
+                if had_children {
+                    return Ok(MiddlewareResult::Replace(message));
+                }
+            }
+            MessageType::Rejected(hash, reason) => {
+                warn!(
+                    "{} rejected {}: {}",
+                    message.source.to_string(),
+                    to_hex(hash),
+                    reason
+                );
+
+                // it's only meant for us
+                return Ok(MiddlewareResult::Halt);
+            }
+            MessageType::GetBlock(hash) => {
+                if let Some(block) = chain.find_block(hash) {
                     postprocessing_sender
-                        .lock()
-                        .unwrap()
-                        .broadcast(message.clone());
+                        .send_to(&message.source.unwrap(), MessageType::MinedBlock(block));
                 }
+
+                // it's only meant for us
+                return Ok(MiddlewareResult::Halt);
             }
         }
+
+        Ok(MiddlewareResult::Continue)
+    }
+
+    fn on_peer_disconnected(
+        &mut self,
+        address: &str,
+        _preprocessing_sender: &Sender<Event>,
+        _postprocessing_sender: Arc<PeerRegistry>,
+        _chain: &mut Blockchain,
+    ) -> Result<(), String> {
+        if self.block_index < self.num_blocks_in_chain {
+            return Err(format!(
+                "{} disconnected before sending the whole chain",
+                address
+            ));
+        }
+
+        Ok(())
     }
 }
@@ -0,0 +1,81 @@
+use std::sync::{mpsc::Sender, Arc};
+
+use crate::{
+    blockchain::{BlockTemplateBuilder, Blockchain, Wallet},
+    networking::{pool::PoolServer, Event, InternalMessage, MessageType, PeerRegistry},
+};
+
+use super::{Middleware, MiddlewareResult};
+
+/// lets remote workers mine through a pool server instead of mining in-process
+pub struct PoolMiddleware {
+    template_builder: BlockTemplateBuilder,
+    pool: PoolServer,
+    // the block the workers currently mine on
+    tip: Option<Vec<u8>>,
+}
+
+impl PoolMiddleware {
+    pub fn new(wallet: Wallet, port: String, share_difficulty: usize) -> Self {
+        Self {
+            template_builder: BlockTemplateBuilder::new(wallet.public_key),
+            pool: PoolServer::new(port, share_difficulty),
+            tip: None,
+        }
+    }
+
+    /// hand out a new template, paying the workers by their recent shares
+    fn update_template(&mut self, chain: &Blockchain) {
+        self.tip = chain.main_chain().last().map(|block| block.hash());
+
+        if let Some(block) = self
+            .template_builder
+            .build_with_payouts(chain, &self.pool.payouts())
+        {
+            self.pool.set_template(block, chain.params.difficulty);
+        }
+    }
+}
+
+impl Middleware for PoolMiddleware {
+    fn name(&self) -> &'static str {
+        "pool"
+    }
+
+    // mined blocks and transactions have to be in the chain before mining on top of them
+    fn run_after(&self) -> Vec<&'static str> {
+        vec!["node", "genesis"]
+    }
+
+    fn on_start(
+        &mut self,
+        preprocessing_sender: &Sender<Event>,
+        _postprocessing_sender: Arc<PeerRegistry>,
+        chain: &mut Blockchain,
+    ) -> Result<(), String> {
+        self.pool.start(preprocessing_sender.clone());
+        self.update_template(chain);
+        Ok(())
+    }
+
+    fn on_message(
+        &mut self,
+        message: &InternalMessage,
+        _preprocessing_sender: &Sender<Event>,
+        _postprocessing_sender: Arc<PeerRegistry>,
+        chain: &mut Blockchain,
+    ) -> Result<MiddlewareResult, String> {
+        // the template would change with every block of the chain
+        if chain.is_receiving() {
+            return Ok(MiddlewareResult::Continue);
+        }
+
+        let tip_changed = chain.main_chain().last().map(|block| block.hash()) != self.tip;
+
+        if tip_changed || matches!(message.message.message_type, MessageType::Transaction(_)) {
+            self.update_template(chain);
+        }
+
+        Ok(MiddlewareResult::Continue)
+    }
+}
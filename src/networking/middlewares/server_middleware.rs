@@ -1,66 +1,60 @@
-use std::sync::{Arc, Mutex};
+use std::sync::{mpsc::Sender, Arc};
 
-use bus::Bus;
 use log::debug;
-use std::sync::mpsc::Sender;
 
 use crate::{
     blockchain::Blockchain,
-    networking::{
-        message::{MessageDest, MessageSource},
-        InternalMessage, MessageType,
-    },
+    networking::{message::MessageDest, Event, InternalMessage, MessageType, PeerRegistry},
 };
 
-use super::Middleware;
+use super::{Middleware, MiddlewareResult};
 
 pub struct ServerMiddleware;
 
 impl Middleware for ServerMiddleware {
+    fn name(&self) -> &'static str {
+        "server"
+    }
+
+    // only forward messages the other middlewares accepted
+    fn run_after(&self) -> Vec<&'static str> {
+        vec!["node", "genesis", "miner"]
+    }
+
     fn on_message(
         &mut self,
         message: &InternalMessage,
-        _preprocessing_sender: &Sender<InternalMessage>,
-        postprocessing_sender: Arc<Mutex<Bus<InternalMessage>>>,
+        _preprocessing_sender: &Sender<Event>,
+        postprocessing_sender: Arc<PeerRegistry>,
         chain: &mut Blockchain,
-    ) {
+    ) -> Result<MiddlewareResult, String> {
         // dont forward connect, sendblockchain and sendblockchainblock messages
         if let MessageType::Connect = message.message.message_type {
             let all_blocks = chain.all_blocks();
 
-            let address = message.source.unwrap();
-            let mut sender = postprocessing_sender.lock().unwrap();
-            sender.broadcast(InternalMessage::new(
-                MessageType::SendBlockchain(all_blocks.len(), chain.unmined_transactions.len()),
-                MessageSource::Localhost,
-                MessageDest::Single(address.clone()),
-            ));
+            let mut messages = vec![MessageType::SendBlockchain(
+                all_blocks.len(),
+                chain.unmined_transactions.len(),
+            )];
 
             for mut block in all_blocks {
                 block.children = vec![];
-                sender.broadcast(InternalMessage::new(
-                    MessageType::SendBlockchainBlock(block),
-                    MessageSource::Localhost,
-                    MessageDest::Single(address.clone()),
-                ));
+                messages.push(MessageType::SendBlockchainBlock(block));
             }
 
             for transaction in &chain.unmined_transactions {
-                sender.broadcast(InternalMessage::new(
-                    MessageType::SendBlockchainTransaction(transaction.clone()),
-                    MessageSource::Localhost,
-                    MessageDest::Single(address.clone()),
-                ));
+                messages.push(MessageType::SendBlockchainTransaction(transaction.clone()));
             }
 
-            return;
-        }
+            postprocessing_sender.serve_sync(&message.source.unwrap(), messages);
 
-        if let MessageType::SendBlockchain(_, _) = message.message.message_type {
-            return;
+            return Ok(MiddlewareResult::Halt);
         }
-        if let MessageType::SendBlockchainBlock(_) = message.message.message_type {
-            return;
+
+        if let MessageType::SendBlockchain(_, _) | MessageType::SendBlockchainBlock(_) =
+            message.message.message_type
+        {
+            return Ok(MiddlewareResult::Halt);
         }
 
         debug!(
@@ -71,6 +65,8 @@ impl Middleware for ServerMiddleware {
         let mut new_message = message.clone();
         new_message.dest = MessageDest::Broadcast;
 
-        postprocessing_sender.lock().unwrap().broadcast(new_message);
+        postprocessing_sender.send(new_message);
+
+        Ok(MiddlewareResult::Continue)
     }
 }
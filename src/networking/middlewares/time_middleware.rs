@@ -0,0 +1,83 @@
+use std::{
+    collections::HashSet,
+    sync::{mpsc::Sender, Arc},
+};
+
+use log::{debug, warn};
+
+use crate::{
+    blockchain::Blockchain,
+    consts::MAX_CLOCK_OFFSET,
+    networking::{Event, InternalMessage, MessageSource, PeerRegistry},
+};
+
+use super::{Middleware, MiddlewareResult};
+
+/// estimates the network's time from the first message of every peer, which is sent right after connecting
+#[derive(Default)]
+pub struct TimeMiddleware {
+    // the peers which didn't send a message yet
+    new_peers: HashSet<String>,
+}
+
+impl Middleware for TimeMiddleware {
+    fn name(&self) -> &'static str {
+        "time"
+    }
+
+    // blocks are checked against the network's time
+    fn run_before(&self) -> Vec<&'static str> {
+        vec!["node", "genesis"]
+    }
+
+    fn on_message(
+        &mut self,
+        message: &InternalMessage,
+        _preprocessing_sender: &Sender<Event>,
+        _postprocessing_sender: Arc<PeerRegistry>,
+        chain: &mut Blockchain,
+    ) -> Result<MiddlewareResult, String> {
+        if let MessageSource::Foreign(address) = &message.source {
+            if self.new_peers.remove(address) {
+                let offset = chain
+                    .network_time
+                    .add_sample(address, message.message.timestamp);
+
+                debug!("The clock of {} is {} ms ahead of ours", address, offset);
+                if offset.unsigned_abs() > MAX_CLOCK_OFFSET.as_millis() {
+                    warn!(
+                        "The clock of {} differs from ours by {} ms, please check your clock",
+                        address, offset
+                    );
+                }
+            }
+        }
+
+        Ok(MiddlewareResult::Continue)
+    }
+
+    fn on_peer_connected(
+        &mut self,
+        address: &str,
+        _preprocessing_sender: &Sender<Event>,
+        _postprocessing_sender: Arc<PeerRegistry>,
+        _chain: &mut Blockchain,
+    ) -> Result<(), String> {
+        self.new_peers.insert(address.to_string());
+
+        Ok(())
+    }
+
+    fn on_peer_disconnected(
+        &mut self,
+        address: &str,
+        _preprocessing_sender: &Sender<Event>,
+        _postprocessing_sender: Arc<PeerRegistry>,
+        chain: &mut Blockchain,
+    ) -> Result<(), String> {
+        self.new_peers.remove(address);
+        chain.network_time.remove_peer(address);
+
+        Ok(())
+    }
+}
@@ -1,17 +1,33 @@
 mod client;
+mod event;
 mod handle_stream;
+mod limits;
 mod message;
 mod middlewares;
+mod networking_config;
 mod networking_manager;
+mod orphan_pool;
+mod peer_registry;
+pub mod pool;
 mod server;
+mod transport;
 
 pub use client::Client;
-pub use handle_stream::handle_stream;
+pub use event::Event;
+pub use handle_stream::{handle_stream, write_message};
+pub use limits::{ConnectionLimiter, Limits, RateLimiter};
 pub use message::{InternalMessage, Message, MessageDest, MessageSource, MessageType};
 pub use middlewares::GenesisMiddleware;
-pub use middlewares::Middleware;
 pub use middlewares::MinerMiddleware;
 pub use middlewares::NodeMiddleware;
+pub use middlewares::PoolMiddleware;
 pub use middlewares::ServerMiddleware;
+pub use middlewares::TimeMiddleware;
+pub use networking_config::NetworkingConfig;
 pub use networking_manager::NetworkingManager;
+pub use orphan_pool::OrphanPool;
+pub use peer_registry::{OverflowPolicy, PeerRegistry};
 pub use server::Server;
+pub use transport::{
+    load_allowed_peers, Connection, ConnectionReader, ConnectionWriter, NodeKey, TransportConfig,
+};
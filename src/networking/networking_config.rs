@@ -0,0 +1,8 @@
+use super::{Limits, OverflowPolicy, TransportConfig};
+
+#[derive(Clone)]
+pub struct NetworkingConfig {
+    pub transport: TransportConfig,
+    pub overflow_policy: OverflowPolicy,
+    pub limits: Limits,
+}
@@ -1,69 +1,87 @@
-use std::{
-    sync::{Arc, Mutex},
-    thread,
-};
+use std::{future::pending, mem, sync::Arc, thread};
 
-use bus::Bus;
-use log::debug;
+use log::{debug, info, warn};
 use std::sync::mpsc::{channel, Receiver, Sender};
+use tokio::{
+    runtime::{Builder, Runtime},
+    signal,
+};
 
-use crate::{blockchain::Blockchain, consts::BUFFER_SIZE, util::LogExpect};
+use crate::{blockchain::Blockchain, util::LogExpect};
 
-use super::{middlewares::Middleware, Client, InternalMessage, Server};
+use super::{
+    middlewares::{order_middlewares, Middleware, MiddlewareResult},
+    Client, Event, InternalMessage, NetworkingConfig, PeerRegistry, Server,
+};
 
 pub struct NetworkingManager {
+    // all connections run as tasks on this single-threaded runtime
+    runtime: Option<Runtime>,
     client: Option<Client>,
     server: Option<Server>,
-    incoming_queue_sender: Sender<InternalMessage>,
-    incoming_queue_receiver: Option<Receiver<InternalMessage>>,
-    outgoing_queue_sender: Arc<Mutex<Bus<InternalMessage>>>,
+    incoming_queue_sender: Sender<Event>,
+    incoming_queue_receiver: Option<Receiver<Event>>,
+    peers: Arc<PeerRegistry>,
     middlewares: Vec<Box<dyn Middleware>>,
 }
 
 impl NetworkingManager {
-    pub fn new(addr: Option<String>, server_port: Option<String>) -> Self {
+    pub fn new(
+        addr: Option<String>,
+        server_port: Option<String>,
+        config: NetworkingConfig,
+    ) -> Self {
+        let runtime = Builder::new_current_thread().enable_all().build().unwrap();
+
         let (incoming_queue_sender, incoming_queue_receiver) = channel();
-        let outgoing_queue_sender = Arc::new(Mutex::new(Bus::new(BUFFER_SIZE)));
+        let peers = Arc::new(PeerRegistry::new(
+            config.overflow_policy,
+            config.limits.max_concurrent_syncs,
+            runtime.handle().clone(),
+        ));
 
         let mut local_server = None;
         let mut local_client = None;
 
         if let Some(port) = server_port {
             local_server = Some(
-                Server::new(
-                    "127.0.0.1:".to_string() + &port,
-                    incoming_queue_sender.clone(),
-                    outgoing_queue_sender.clone(),
-                )
-                .log_expect(&format!(
-                    "The port at {} is already in use. Please use another port",
-                    port
-                )),
+                runtime
+                    .block_on(Server::new(
+                        "127.0.0.1:".to_string() + &port,
+                        config.transport.clone(),
+                        config.limits.clone(),
+                        incoming_queue_sender.clone(),
+                        peers.clone(),
+                    ))
+                    .log_expect(&format!(
+                        "The port at {} is already in use. Please use another port",
+                        port
+                    )),
             );
         }
 
-        if addr.is_some() {
-            let client_addr = addr.unwrap();
+        if let Some(client_addr) = addr {
             local_client = Some(
-                Client::new(
-                    client_addr.clone(),
-                    incoming_queue_sender.clone(),
-                    outgoing_queue_sender.lock().unwrap().add_rx(),
-                )
-                .log_expect(&format!("Server at {} unavailable", client_addr)),
+                runtime
+                    .block_on(Client::new(
+                        client_addr.clone(),
+                        &config.transport,
+                        incoming_queue_sender.clone(),
+                        peers.clone(),
+                    ))
+                    .log_expect(&format!("Server at {} unavailable", client_addr)),
             );
         }
 
-        let networking_manager = Self {
+        Self {
+            runtime: Some(runtime),
             client: local_client,
             server: local_server,
             incoming_queue_sender,
             incoming_queue_receiver: Some(incoming_queue_receiver),
-            outgoing_queue_sender,
+            peers,
             middlewares: vec![],
-        };
-
-        networking_manager
+        }
     }
 
     pub fn add_middleware(&mut self, middleware: impl Middleware + 'static) {
@@ -71,18 +89,43 @@ impl NetworkingManager {
     }
 
     fn start_networking_event_loop(&mut self, chain: &mut Blockchain) {
-        loop {
-            let message = self
-                .incoming_queue_receiver
-                .as_ref()
-                .unwrap()
-                .recv()
-                .unwrap();
-            self.run_middlewares(message, chain);
+        let receiver = self.incoming_queue_receiver.take().unwrap();
+
+        while let Ok(event) = receiver.recv() {
+            if let Event::Shutdown = event {
+                self.shutdown(chain);
+                break;
+            }
+
+            self.handle_event(event, chain);
         }
     }
 
-    pub fn run_middlewares(&mut self, message: InternalMessage, chain: &mut Blockchain) {
+    pub fn handle_event(&mut self, event: Event, chain: &mut Blockchain) {
+        let peers = self.peers.clone();
+        let sender = &self.incoming_queue_sender;
+
+        match event {
+            Event::Message(message) => self.run_middlewares(message, chain),
+            Event::PeerConnected(address) => {
+                for middleware in &mut self.middlewares {
+                    let result =
+                        middleware.on_peer_connected(&address, sender, peers.clone(), chain);
+                    log_middleware_error(middleware.as_ref(), result);
+                }
+            }
+            Event::PeerDisconnected(address) => {
+                for middleware in &mut self.middlewares {
+                    let result =
+                        middleware.on_peer_disconnected(&address, sender, peers.clone(), chain);
+                    log_middleware_error(middleware.as_ref(), result);
+                }
+            }
+            Event::Shutdown => self.shutdown(chain),
+        }
+    }
+
+    fn run_middlewares(&mut self, mut message: InternalMessage, chain: &mut Blockchain) {
         debug!(
             "Received a {} message from {} to {}",
             message.message.message_type.to_string(),
@@ -91,46 +134,79 @@ impl NetworkingManager {
         );
 
         for middleware in &mut self.middlewares {
-            middleware.on_message(
+            match middleware.on_message(
                 &message,
                 &self.incoming_queue_sender,
-                self.outgoing_queue_sender.clone(),
+                self.peers.clone(),
                 chain,
-            );
+            ) {
+                Ok(MiddlewareResult::Continue) => {}
+                Ok(MiddlewareResult::Halt) => break,
+                Ok(MiddlewareResult::Replace(new_message)) => message = new_message,
+                Err(err) => {
+                    warn!("{} middleware: {}", middleware.name(), err);
+                    break;
+                }
+            }
         }
     }
 
+    pub fn shutdown(&mut self, chain: &mut Blockchain) {
+        info!("Shutting down");
+
+        for middleware in &mut self.middlewares {
+            let result = middleware.on_shutdown(chain);
+            log_middleware_error(middleware.as_ref(), result);
+        }
+    }
+
+    /// start networking and handle all events until the node is shut down with ctrl-c
     pub fn start_networking(&mut self, chain: &mut Blockchain) {
-        self.start_client_server();
+        let sender = self.incoming_queue_sender.clone();
+        self.runtime.as_ref().unwrap().spawn(async move {
+            if signal::ctrl_c().await.is_ok() {
+                sender.send(Event::Shutdown).unwrap();
+            }
+        });
+
+        self.start_client_server(chain);
         self.start_networking_event_loop(chain);
     }
 
-    pub fn start_client_server(&mut self) {
-        if let Some(client) = &mut self.client {
-            client.start_networking();
-        }
-        if let Some(server) = &mut self.server {
-            server.start_networking();
+    pub fn start_client_server(&mut self, chain: &mut Blockchain) {
+        self.middlewares = order_middlewares(mem::take(&mut self.middlewares))
+            .log_expect("Can't order the middlewares");
+
+        for middleware in &mut self.middlewares {
+            let result =
+                middleware.on_start(&self.incoming_queue_sender, self.peers.clone(), chain);
+            log_middleware_error(middleware.as_ref(), result);
         }
 
-        let mut receiver = self.outgoing_queue_sender.lock().unwrap().add_rx();
+        let runtime = self.runtime.take().unwrap();
 
-        thread::spawn(move || loop {
-            let msg = receiver.recv().unwrap();
-            debug!(
-                "Sending a {} message from {} to {}",
-                msg.message.message_type.to_string(),
-                msg.source.to_string(),
-                msg.dest.to_string()
-            )
-        });
+        if let Some(client) = self.client.take() {
+            runtime.spawn(client.start_networking());
+        }
+        if let Some(server) = self.server.take() {
+            runtime.spawn(server.start_networking());
+        }
+
+        // the event loop runs forever in its own thread
+        thread::spawn(move || runtime.block_on(pending::<()>()));
     }
 
-    pub fn get_sender(&self) -> Arc<Mutex<Bus<InternalMessage>>> {
-        self.outgoing_queue_sender.clone()
+    pub fn get_sender(&self) -> Arc<PeerRegistry> {
+        self.peers.clone()
     }
 
-    pub fn get_receiver(&mut self) -> Option<Receiver<InternalMessage>> {
+    pub fn get_receiver(&mut self) -> Option<Receiver<Event>> {
         self.incoming_queue_receiver.take()
     }
 }
+
+fn log_middleware_error(middleware: &dyn Middleware, result: Result<(), String>) {
+    if let Err(err) = result {
+        warn!("{} middleware: {}", middleware.name(), err);
+    }
+}
@@ -0,0 +1,219 @@
+use std::{collections::VecDeque, sync::mpsc::Sender, time::Instant};
+
+use log::{debug, info};
+
+use crate::{
+    blockchain::{Block, BlockError, Blockchain, ChainParams},
+    consts::{MAX_ORPHAN_BLOCKS, ORPHAN_BLOCK_EXPIRY},
+    util::to_hex,
+};
+
+use super::{Event, InternalMessage, MessageDest, MessageSource, MessageType, PeerRegistry};
+
+/// blocks which arrived before the block they build on, waiting for it
+/// - only a limited number is kept, the oldest are dropped first
+/// - they expire after a while
+#[derive(Default)]
+pub struct OrphanPool {
+    // the oldest first
+    orphans: VecDeque<Orphan>,
+}
+
+struct Orphan {
+    hash: Vec<u8>,
+    block: Block,
+    source: MessageSource,
+    arrived: Instant,
+}
+
+impl OrphanPool {
+    /// push a block from the network, keeping it if the block it builds on is unknown
+    ///
+    /// returns whether it was connected, it's not if it's an orphan or known already
+    pub fn push_block(
+        &mut self,
+        block: &Block,
+        source: &MessageSource,
+        chain: &mut Blockchain,
+        preprocessing_sender: &Sender<Event>,
+        peers: &PeerRegistry,
+    ) -> Result<bool, BlockError> {
+        match chain.push_block(block.clone()) {
+            Ok(()) => {
+                self.connect_children(&block.hash(), preprocessing_sender);
+                Ok(true)
+            }
+            Err(BlockError::AlreadyKnown) => Ok(false),
+            Err(BlockError::UnknownParent) => {
+                self.add(block.clone(), source, &chain.params, peers)?;
+                Ok(false)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// keep a block whose parent isn't in the chain and ask the peer it came from for the parent
+    ///
+    /// only the block's own checks are done, the rest is verified once it can be connected
+    fn add(
+        &mut self,
+        block: Block,
+        source: &MessageSource,
+        params: &ChainParams,
+        peers: &PeerRegistry,
+    ) -> Result<(), BlockError> {
+        self.prune();
+
+        block.check_limits(params)?;
+        if !block.verify_nonce(params) {
+            return Err(BlockError::BadProofOfWork);
+        }
+
+        let hash = block.hash();
+        if self.contains(&hash) {
+            return Ok(());
+        }
+
+        // the parent may be an orphan itself, then its parent was requested already
+        if !self.contains(&block.prev_hash) {
+            if let MessageSource::Foreign(address) = source {
+                info!(
+                    "Requesting the block {} from {}",
+                    to_hex(&block.prev_hash),
+                    address
+                );
+                peers.send_to(address, MessageType::GetBlock(block.prev_hash.clone()));
+            }
+        }
+
+        debug!("Keeping the orphan block {}", to_hex(&hash));
+        self.orphans.push_back(Orphan {
+            hash,
+            block,
+            source: source.clone(),
+            arrived: Instant::now(),
+        });
+
+        if self.orphans.len() > MAX_ORPHAN_BLOCKS {
+            self.orphans.pop_front();
+        }
+
+        Ok(())
+    }
+
+    pub fn contains(&self, hash: &[u8]) -> bool {
+        self.orphans.iter().any(|orphan| orphan.hash == hash)
+    }
+
+    /// hand the orphans building on the block back to the middlewares, as if they arrived just now,
+    /// so connecting them connects their own orphans as well
+    fn connect_children(&mut self, hash: &[u8], preprocessing_sender: &Sender<Event>) {
+        let (children, orphans): (Vec<_>, Vec<_>) = self
+            .orphans
+            .drain(..)
+            .partition(|orphan| orphan.block.prev_hash == hash);
+        self.orphans = orphans.into();
+
+        for orphan in children {
+            debug!("Connecting the orphan block {}", to_hex(&orphan.hash));
+
+            preprocessing_sender
+                .send(Event::Message(InternalMessage::new(
+                    MessageType::MinedBlock(orphan.block),
+                    orphan.source,
+                    MessageDest::Localhost,
+                )))
+                .unwrap();
+        }
+    }
+
+    fn prune(&mut self) {
+        self.orphans
+            .retain(|orphan| orphan.arrived.elapsed() < ORPHAN_BLOCK_EXPIRY);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::channel;
+
+    use tokio::runtime::Builder;
+
+    use super::super::OverflowPolicy;
+    use super::*;
+
+    fn peers() -> PeerRegistry {
+        let runtime = Builder::new_current_thread().build().unwrap();
+
+        PeerRegistry::new(OverflowPolicy::Drop, 1, runtime.handle().clone())
+    }
+
+    /// an empty block building on a block nobody knows
+    fn orphan(parent: u32) -> Block {
+        Block::new(parent.to_be_bytes().repeat(8), vec![])
+    }
+
+    fn add(pool: &mut OrphanPool, block: &Block) {
+        pool.add(
+            block.clone(),
+            &MessageSource::Localhost,
+            &ChainParams::regtest(),
+            &peers(),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn only_the_newest_orphans_are_kept() {
+        let mut pool = OrphanPool::default();
+        let blocks: Vec<_> = (0..=MAX_ORPHAN_BLOCKS as u32).map(orphan).collect();
+
+        for block in &blocks {
+            add(&mut pool, block);
+        }
+        add(&mut pool, &blocks[MAX_ORPHAN_BLOCKS]);
+
+        assert_eq!(pool.orphans.len(), MAX_ORPHAN_BLOCKS);
+        assert!(!pool.contains(&blocks[0].hash()));
+        assert!(pool.contains(&blocks[1].hash()));
+        assert!(pool.contains(&blocks[MAX_ORPHAN_BLOCKS].hash()));
+    }
+
+    #[test]
+    fn orphans_expire() {
+        let mut pool = OrphanPool::default();
+        let (expired, almost_expired, new) = (orphan(0), orphan(1), orphan(2));
+
+        add(&mut pool, &expired);
+        add(&mut pool, &almost_expired);
+        pool.orphans[0].arrived = Instant::now() - ORPHAN_BLOCK_EXPIRY;
+        pool.orphans[1].arrived = Instant::now() - ORPHAN_BLOCK_EXPIRY / 2;
+        add(&mut pool, &new);
+
+        assert!(!pool.contains(&expired.hash()));
+        assert!(pool.contains(&almost_expired.hash()));
+        assert!(pool.contains(&new.hash()));
+    }
+
+    #[test]
+    fn children_are_handed_back_once_their_parent_is_connected() {
+        let mut pool = OrphanPool::default();
+        let (child, other) = (orphan(0), orphan(1));
+        add(&mut pool, &child);
+        add(&mut pool, &other);
+
+        let (sender, receiver) = channel();
+        pool.connect_children(&child.prev_hash, &sender);
+
+        match receiver.try_recv() {
+            Ok(Event::Message(message)) => {
+                assert!(matches!(message.message.message_type,
+                    MessageType::MinedBlock(block) if block.hash() == child.hash()))
+            }
+            _ => panic!("the child wasn't handed back"),
+        }
+        assert!(receiver.try_recv().is_err());
+        assert!(!pool.contains(&child.hash()));
+        assert!(pool.contains(&other.hash()));
+    }
+}
@@ -0,0 +1,212 @@
+use std::{
+    collections::{HashMap, HashSet},
+    net::{IpAddr, SocketAddr},
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use log::{debug, info, warn};
+use tokio::{
+    runtime::Handle,
+    sync::{
+        mpsc::{channel, error::TrySendError, Receiver, Sender},
+        Semaphore,
+    },
+};
+
+use crate::consts::{BAN_DURATION, MAX_BAN_SCORE, PEER_QUEUE_SIZE};
+
+use super::{InternalMessage, Message, MessageSource, MessageType};
+
+/// what happens when a peer doesn't read its messages fast enough
+#[derive(Clone, Copy, Debug)]
+pub enum OverflowPolicy {
+    /// drop the message for this peer only
+    Drop,
+    /// disconnect the peer
+    Disconnect,
+}
+
+impl FromStr for OverflowPolicy {
+    type Err = String;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        match string.to_lowercase().as_str() {
+            "drop" => Ok(OverflowPolicy::Drop),
+            "disconnect" => Ok(OverflowPolicy::Disconnect),
+            _ => Err(format!(
+                "{} is not an overflow policy (drop or disconnect)",
+                string
+            )),
+        }
+    }
+}
+
+/// the bounded outgoing queues of all connected peers
+/// messages are put directly into the queue of every peer they should be send to, without ever blocking
+pub struct PeerRegistry {
+    queues: Mutex<HashMap<String, Sender<Message>>>,
+    overflow_policy: OverflowPolicy,
+    runtime: Handle,
+    // one permit per full sync which may be served at the same time
+    syncs: Arc<Semaphore>,
+    // peers which already got a full sync on their connection
+    synced: Mutex<HashSet<String>>,
+    // how much the connected peers misbehaved
+    ban_scores: Mutex<HashMap<String, u32>>,
+    // the ips which can't connect until the time
+    banned: Mutex<HashMap<IpAddr, Instant>>,
+}
+
+impl PeerRegistry {
+    pub fn new(
+        overflow_policy: OverflowPolicy,
+        max_concurrent_syncs: usize,
+        runtime: Handle,
+    ) -> Self {
+        Self {
+            queues: Mutex::new(HashMap::new()),
+            overflow_policy,
+            runtime,
+            syncs: Arc::new(Semaphore::new(max_concurrent_syncs)),
+            synced: Mutex::new(HashSet::new()),
+            ban_scores: Mutex::new(HashMap::new()),
+            banned: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn add(&self, address: &str) -> Receiver<Message> {
+        let (sender, receiver) = channel(PEER_QUEUE_SIZE);
+        self.queues
+            .lock()
+            .unwrap()
+            .insert(address.to_string(), sender);
+
+        receiver
+    }
+
+    /// dropping the queue cancels the connection to the peer
+    pub fn remove(&self, address: &str) {
+        self.queues.lock().unwrap().remove(address);
+        self.synced.lock().unwrap().remove(address);
+        self.ban_scores.lock().unwrap().remove(address);
+    }
+
+    /// tell the peer a message came from why we rejected it and add to the peer's ban score,
+    /// a peer misbehaving too much gets disconnected and its ip banned for a while
+    pub fn reject(&self, source: &MessageSource, hash: Vec<u8>, reason: &str, ban_score: u32) {
+        let address = match source {
+            MessageSource::Foreign(address) => address,
+            MessageSource::Localhost => return,
+        };
+
+        self.send_to(address, MessageType::Rejected(hash, reason.to_string()));
+
+        if ban_score == 0 {
+            return;
+        }
+
+        let score = {
+            let mut ban_scores = self.ban_scores.lock().unwrap();
+            let score = ban_scores.entry(address.clone()).or_insert(0);
+            *score += ban_score;
+            *score
+        };
+        info!("The ban score of {} is {} now", address, score);
+
+        if score >= MAX_BAN_SCORE {
+            warn!("Banning {} for {:?}", address, BAN_DURATION);
+
+            if let Ok(socket_address) = address.parse::<SocketAddr>() {
+                self.banned
+                    .lock()
+                    .unwrap()
+                    .insert(socket_address.ip(), Instant::now() + BAN_DURATION);
+            }
+            // the rejection is still sent before the connection is shut down
+            self.remove(address);
+        }
+    }
+
+    /// send a message to a single peer, it's dropped if the peer's queue is full
+    pub fn send_to(&self, address: &str, message_type: MessageType) {
+        if let Some(queue) = self.queues.lock().unwrap().get(address) {
+            let _ = queue.try_send(Message::new(message_type));
+        }
+    }
+
+    pub fn is_banned(&self, ip: IpAddr) -> bool {
+        let mut banned = self.banned.lock().unwrap();
+        banned.retain(|_, until| *until > Instant::now());
+
+        banned.contains_key(&ip)
+    }
+
+    /// send a full sync to a peer, waiting for free space in its queue instead of dropping messages
+    /// - only a limited number of syncs is served at the same time
+    /// - every connection gets only one sync
+    pub fn serve_sync(&self, address: &str, messages: Vec<MessageType>) {
+        if !self.synced.lock().unwrap().insert(address.to_string()) {
+            warn!("{} requested a second sync, ignoring it", address);
+            return;
+        }
+
+        let queue = match self.queues.lock().unwrap().get(address) {
+            Some(queue) => queue.clone(),
+            None => return,
+        };
+
+        let address = address.to_string();
+        let syncs = self.syncs.clone();
+
+        self.runtime.spawn(async move {
+            let _permit = syncs.acquire().await.unwrap();
+
+            info!("Sending the chain to {}", address);
+
+            for message_type in messages {
+                if queue.send(Message::new(message_type)).await.is_err() {
+                    // the connection was shut down
+                    break;
+                }
+            }
+        });
+    }
+
+    pub fn send(&self, message: InternalMessage) {
+        debug!(
+            "Sending a {} message from {} to {}",
+            message.message.message_type.to_string(),
+            message.source.to_string(),
+            message.dest.to_string()
+        );
+
+        self.queues.lock().unwrap().retain(|address, queue| {
+            !message.should_be_send_to(address) || self.enqueue(address, queue, &message)
+        });
+    }
+
+    /// returns whether the peer should stay connected
+    fn enqueue(&self, address: &str, queue: &Sender<Message>, message: &InternalMessage) -> bool {
+        match queue.try_send(message.message.clone()) {
+            Ok(_) => true,
+            Err(TrySendError::Full(_)) => match self.overflow_policy {
+                OverflowPolicy::Drop => {
+                    warn!(
+                        "The queue to {} is full, dropping a {} message",
+                        address,
+                        message.message.message_type.to_string()
+                    );
+                    true
+                }
+                OverflowPolicy::Disconnect => {
+                    warn!("The queue to {} is full, disconnecting", address);
+                    false
+                }
+            },
+            // the connection was shut down
+            Err(TrySendError::Closed(_)) => false,
+        }
+    }
+}
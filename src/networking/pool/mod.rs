@@ -0,0 +1,7 @@
+mod pool_message;
+mod pool_server;
+mod shares;
+
+pub use pool_message::{read_line, write_line, PoolRequest, PoolResponse};
+pub use pool_server::PoolServer;
+pub use shares::Shares;
@@ -0,0 +1,80 @@
+use std::io::{Error, ErrorKind, Result};
+
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::consts::MAX_POOL_LINE_SIZE;
+
+/// what a worker sends to the pool
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "method", rename_all = "snake_case")]
+pub enum PoolRequest {
+    /// the worker's rewards are paid to the PEM-encoded public key
+    Login {
+        worker: String,
+        payout_key: String,
+    },
+    Submit {
+        job_id: u64,
+        nonce: u64,
+    },
+}
+
+/// what the pool sends to a worker
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PoolResponse {
+    /// search a nonce for the header whose hash starts with share_difficulty null bytes
+    ///
+    /// the header is hex encoded bincode, the nonce is its last 8 bytes (little endian)
+    Job {
+        job_id: u64,
+        header: String,
+        share_difficulty: usize,
+    },
+    /// block is true if the share was good enough to be a block
+    Accepted {
+        job_id: u64,
+        block: bool,
+    },
+    Rejected {
+        reason: String,
+    },
+}
+
+/// messages are single lines of json
+pub async fn write_line(
+    writer: &mut (impl AsyncWrite + Unpin),
+    message: &impl Serialize,
+) -> Result<()> {
+    let mut line = serde_json::to_vec(message)?;
+    line.push(b'\n');
+
+    writer.write_all(&line).await
+}
+
+/// returns None when the connection was shut down
+pub async fn read_line(reader: &mut (impl AsyncBufRead + Unpin)) -> Result<Option<String>> {
+    let mut line = vec![];
+    (&mut *reader)
+        .take(MAX_POOL_LINE_SIZE as u64 + 1)
+        .read_until(b'\n', &mut line)
+        .await?;
+
+    if line.is_empty() {
+        return Ok(None);
+    }
+    if line.len() > MAX_POOL_LINE_SIZE {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!(
+                "a line of more than {} bytes is too long",
+                MAX_POOL_LINE_SIZE
+            ),
+        ));
+    }
+
+    String::from_utf8(line)
+        .map(Some)
+        .map_err(|err| Error::new(ErrorKind::InvalidData, err))
+}
@@ -0,0 +1,395 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    future::pending,
+    io::Result,
+    net::SocketAddr,
+    sync::{mpsc::Sender, Arc, Mutex},
+    thread,
+};
+
+use log::{debug, info, warn};
+use rand::random;
+use rsa::{pkcs8::FromPublicKey, RsaPublicKey};
+use tokio::{
+    io::BufReader,
+    net::{TcpListener, TcpStream},
+    runtime::{Builder, Runtime},
+    sync::{mpsc, watch},
+    time::interval,
+};
+
+use crate::{
+    blockchain::Block,
+    consts::{MAX_POOL_JOBS_PER_WORKER, POOL_JOB_REFRESH_INTERVAL},
+    networking::{Event, InternalMessage, MessageDest, MessageSource, MessageType},
+    util::{time_since_unix_epoch, to_hex, LogExpect},
+};
+
+use super::{read_line, write_line, PoolRequest, PoolResponse, Shares};
+
+struct Job {
+    worker: String,
+    block: Block,
+}
+
+struct PoolState {
+    template: Option<Block>,
+    // the number of null bytes the hash of a block has to start with
+    difficulty: usize,
+    // the jobs for the current template
+    jobs: HashMap<u64, Job>,
+    // the ids of every worker's jobs, the oldest first
+    worker_jobs: HashMap<String, VecDeque<u64>>,
+    // the job ids are also the extra nonces, so no two workers search the same nonces
+    next_job_id: u64,
+    submitted: HashSet<(u64, u64)>,
+    shares: Shares,
+}
+
+impl PoolState {
+    fn new_job(&mut self, worker: &str, share_difficulty: usize) -> Option<PoolResponse> {
+        let mut block = self.template.clone()?;
+
+        let job_id = self.next_job_id;
+        self.next_job_id = self.next_job_id.wrapping_add(1);
+
+        block.set_extra_nonce(job_id);
+        block.date = block.date.max(time_since_unix_epoch());
+        block.nonce = 0;
+
+        // json can't carry the u128 date in every case
+        let header = to_hex(&bincode::serialize(&block.header()).unwrap());
+        self.jobs.insert(
+            job_id,
+            Job {
+                worker: worker.to_string(),
+                block,
+            },
+        );
+
+        // every job holds a whole block, so a worker's old jobs are dropped
+        let worker_jobs = self.worker_jobs.entry(worker.to_string()).or_default();
+        worker_jobs.push_back(job_id);
+        while worker_jobs.len() > MAX_POOL_JOBS_PER_WORKER {
+            if let Some(old_job_id) = worker_jobs.pop_front() {
+                self.jobs.remove(&old_job_id);
+                self.submitted.retain(|(job_id, _)| *job_id != old_job_id);
+            }
+        }
+
+        Some(PoolResponse::Job {
+            job_id,
+            header,
+            share_difficulty,
+        })
+    }
+
+    /// returns the block if the share solves it
+    fn submit(
+        &mut self,
+        worker: &str,
+        job_id: u64,
+        nonce: u64,
+        share_difficulty: usize,
+    ) -> std::result::Result<Option<Block>, String> {
+        let job = match self.jobs.get(&job_id) {
+            Some(job) if job.worker == worker => job,
+            _ => return Err("unknown or stale job".to_string()),
+        };
+
+        if !self.submitted.insert((job_id, nonce)) {
+            return Err("duplicate share".to_string());
+        }
+
+        let mut block = job.block.clone();
+        block.nonce = nonce;
+
+        // on easy networks a block can be easier than a share
+        let header = block.header();
+        if header.meets_difficulty(self.difficulty) {
+            // the other jobs would only solve competing blocks until the template changes
+            self.jobs.clear();
+            self.worker_jobs.clear();
+            return Ok(Some(block));
+        }
+
+        if !header.meets_difficulty(share_difficulty) {
+            return Err("the share's hash is too high".to_string());
+        }
+
+        Ok(None)
+    }
+}
+
+/// hands out work to remote mining workers over a line-based json protocol
+/// and counts their shares
+///
+/// a share is a nonce for which the header's hash starts with share_difficulty null bytes,
+/// some of them solve the block
+pub struct PoolServer {
+    runtime: Option<Runtime>,
+    listener: Option<TcpListener>,
+    share_difficulty: usize,
+    state: Arc<Mutex<PoolState>>,
+    template_sender: watch::Sender<()>,
+}
+
+impl PoolServer {
+    pub fn new(port: String, share_difficulty: usize) -> Self {
+        let runtime = Builder::new_current_thread().enable_all().build().unwrap();
+
+        // workers usually run on other machines
+        let listener = runtime
+            .block_on(TcpListener::bind("0.0.0.0:".to_string() + &port))
+            .log_expect(&format!(
+                "The port at {} is already in use. Please use another port",
+                port
+            ));
+
+        let (template_sender, _) = watch::channel(());
+
+        Self {
+            runtime: Some(runtime),
+            listener: Some(listener),
+            share_difficulty,
+            state: Arc::new(Mutex::new(PoolState {
+                template: None,
+                difficulty: 0,
+                jobs: HashMap::new(),
+                worker_jobs: HashMap::new(),
+                next_job_id: random(),
+                submitted: HashSet::new(),
+                shares: Shares::default(),
+            })),
+            template_sender,
+        }
+    }
+
+    /// accept workers in an own thread, solved blocks are sent to block_sender
+    pub fn start(&mut self, block_sender: Sender<Event>) {
+        let runtime = self.runtime.take().unwrap();
+        let listener = self.listener.take().unwrap();
+
+        let share_difficulty = self.share_difficulty;
+        let state = self.state.clone();
+        let template_receiver = self.template_sender.subscribe();
+
+        runtime.spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((stream, address)) => {
+                        info!("New pool worker connection from {}", address);
+
+                        tokio::spawn(handle_worker(
+                            stream,
+                            address,
+                            share_difficulty,
+                            state.clone(),
+                            template_receiver.clone(),
+                            block_sender.clone(),
+                        ));
+                    }
+                    Err(err) => warn!("Couldn't accept a pool worker: {}", err),
+                }
+            }
+        });
+
+        thread::spawn(move || runtime.block_on(pending::<()>()));
+    }
+
+    /// all workers get a new job for the template, jobs for older templates become stale
+    pub fn set_template(&self, block: Block, difficulty: usize) {
+        let mut state = self.state.lock().unwrap();
+
+        state.template = Some(block);
+        state.difficulty = difficulty;
+        state.jobs.clear();
+        state.worker_jobs.clear();
+        state.submitted.clear();
+
+        self.template_sender.send_replace(());
+    }
+
+    pub fn payouts(&self) -> Vec<(RsaPublicKey, u64)> {
+        self.state.lock().unwrap().shares.payouts()
+    }
+}
+
+async fn handle_worker(
+    stream: TcpStream,
+    address: SocketAddr,
+    share_difficulty: usize,
+    state: Arc<Mutex<PoolState>>,
+    mut template_receiver: watch::Receiver<()>,
+    block_sender: Sender<Event>,
+) {
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+
+    // read in an own task, a line read only partially would get lost when another branch of select! wins
+    let (line_sender, mut lines) = mpsc::channel(1);
+    let reading = tokio::spawn(async move {
+        loop {
+            let line = read_line(&mut reader).await.transpose();
+            let stop = !matches!(line, Some(Ok(_)));
+
+            if line_sender.send(line).await.is_err() || stop {
+                break;
+            }
+        }
+    });
+
+    let mut worker: Option<String> = None;
+    let mut refresh = interval(POOL_JOB_REFRESH_INTERVAL);
+
+    let result: Result<()> = async {
+        loop {
+            // a new job for a new template or to keep the timestamp fresh
+            let new_job = tokio::select! {
+                line = lines.recv() => {
+                    let line = match line.flatten() {
+                        Some(line) => line?,
+                        None => return Ok(()),
+                    };
+
+                    let (response, logged_in) = match serde_json::from_str(&line) {
+                        Ok(request) => {
+                            let is_login = matches!(request, PoolRequest::Login { .. });
+                            let response = handle_request(request, &mut worker, &state, &block_sender, share_difficulty);
+
+                            // the answer to a successful login is the first job
+                            (response, is_login)
+                        }
+                        Err(err) => (
+                            Some(PoolResponse::Rejected {
+                                reason: format!("invalid request: {}", err),
+                            }),
+                            false,
+                        ),
+                    };
+
+                    match response {
+                        Some(response) => {
+                            write_line(&mut writer, &response).await?;
+                            false
+                        }
+                        None => logged_in,
+                    }
+                }
+                changed = template_receiver.changed(), if worker.is_some() => {
+                    if changed.is_err() {
+                        return Ok(());
+                    }
+                    true
+                }
+                _ = refresh.tick(), if worker.is_some() => true,
+            };
+
+            if !new_job {
+                continue;
+            }
+
+            let job = match &worker {
+                Some(worker) => state.lock().unwrap().new_job(worker, share_difficulty),
+                None => None,
+            };
+            if let Some(job) = job {
+                write_line(&mut writer, &job).await?;
+            }
+        }
+    }
+    .await;
+
+    reading.abort();
+
+    if let Err(err) = result {
+        warn!("Dropping the pool worker at {}: {}", address, err);
+    }
+
+    match worker.and_then(|worker| {
+        let stats = state.lock().unwrap().shares.worker_stats(&worker)?;
+        Some((worker, stats))
+    }) {
+        Some((worker, (accepted, rejected))) => info!(
+            "Worker {} at {} disconnected, it has {} accepted and {} rejected shares",
+            worker, address, accepted, rejected
+        ),
+        None => info!("The pool worker at {} disconnected", address),
+    }
+}
+
+/// returns no response for a successful login
+fn handle_request(
+    request: PoolRequest,
+    worker: &mut Option<String>,
+    state: &Mutex<PoolState>,
+    block_sender: &Sender<Event>,
+    share_difficulty: usize,
+) -> Option<PoolResponse> {
+    let mut state = state.lock().unwrap();
+
+    match request {
+        PoolRequest::Login {
+            worker: name,
+            payout_key,
+        } => {
+            let payout_key = match RsaPublicKey::from_public_key_pem(&payout_key) {
+                Ok(payout_key) => payout_key,
+                Err(err) => {
+                    return Some(PoolResponse::Rejected {
+                        reason: format!("invalid payout key: {}", err),
+                    })
+                }
+            };
+
+            if let Err(reason) = state.shares.login(&name, payout_key) {
+                return Some(PoolResponse::Rejected { reason });
+            }
+            info!("Worker {} logged in", name);
+            *worker = Some(name);
+
+            None
+        }
+        PoolRequest::Submit { job_id, nonce } => {
+            let name = match worker {
+                Some(name) => name,
+                None => {
+                    return Some(PoolResponse::Rejected {
+                        reason: "not logged in".to_string(),
+                    })
+                }
+            };
+
+            match state.submit(name, job_id, nonce, share_difficulty) {
+                Ok(block) => {
+                    state.shares.accept(name);
+                    debug!("Accepted a share from {}", name);
+
+                    let solved = block.is_some();
+                    if let Some(block) = block {
+                        info!("Worker {} solved a block: {}", name, block.nonce);
+
+                        block_sender
+                            .send(Event::Message(InternalMessage::new(
+                                MessageType::MinedBlock(block),
+                                MessageSource::Localhost,
+                                MessageDest::Localhost,
+                            )))
+                            .unwrap();
+                    }
+
+                    Some(PoolResponse::Accepted {
+                        job_id,
+                        block: solved,
+                    })
+                }
+                Err(reason) => {
+                    state.shares.reject(name);
+                    debug!("Rejected a share from {}: {}", name, reason);
+
+                    Some(PoolResponse::Rejected { reason })
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use rsa::RsaPublicKey;
+
+use crate::consts::POOL_PAYOUT_WINDOW;
+
+struct Worker {
+    payout_key: RsaPublicKey,
+    accepted: u64,
+    rejected: u64,
+}
+
+/// counts the shares of every worker
+///
+/// block rewards are split by the last POOL_PAYOUT_WINDOW accepted shares,
+/// so a worker keeps being paid for its shares after the block it mined on
+#[derive(Default)]
+pub struct Shares {
+    window: VecDeque<String>,
+    workers: HashMap<String, Worker>,
+}
+
+impl Shares {
+    /// a worker logging in again keeps its shares,
+    /// the name stays bound to the first payout key, so nobody else can take over its shares
+    pub fn login(&mut self, worker: &str, payout_key: RsaPublicKey) -> Result<(), String> {
+        match self.workers.get(worker) {
+            Some(stats) if stats.payout_key != payout_key => Err(format!(
+                "the worker {} logged in with another payout key",
+                worker
+            )),
+            Some(_) => Ok(()),
+            None => {
+                self.workers.insert(
+                    worker.to_string(),
+                    Worker {
+                        payout_key,
+                        accepted: 0,
+                        rejected: 0,
+                    },
+                );
+                Ok(())
+            }
+        }
+    }
+
+    pub fn accept(&mut self, worker: &str) {
+        if let Some(stats) = self.workers.get_mut(worker) {
+            stats.accepted += 1;
+
+            self.window.push_back(worker.to_string());
+            if self.window.len() > POOL_PAYOUT_WINDOW {
+                self.window.pop_front();
+            }
+        }
+    }
+
+    pub fn reject(&mut self, worker: &str) {
+        if let Some(stats) = self.workers.get_mut(worker) {
+            stats.rejected += 1;
+        }
+    }
+
+    /// (accepted, rejected) shares of the worker since the pool started
+    pub fn worker_stats(&self, worker: &str) -> Option<(u64, u64)> {
+        self.workers
+            .get(worker)
+            .map(|stats| (stats.accepted, stats.rejected))
+    }
+
+    /// the payout keys of the workers weighted by their shares in the window
+    pub fn payouts(&self) -> Vec<(RsaPublicKey, u64)> {
+        // ordered by name to build the same coinbase for the same shares
+        let mut counts: BTreeMap<&str, u64> = BTreeMap::new();
+        for worker in &self.window {
+            *counts.entry(worker).or_insert(0) += 1;
+        }
+
+        counts
+            .into_iter()
+            .map(|(worker, count)| (self.workers[worker].payout_key.clone(), count))
+            .collect()
+    }
+}
@@ -1,55 +1,85 @@
 use std::{
     io::Result,
-    net::TcpListener,
-    sync::{Arc, Mutex},
-    thread,
+    sync::{mpsc::Sender, Arc},
 };
 
-use bus::Bus;
-use log::{error, info};
-use std::sync::mpsc::Sender;
+use log::{error, info, warn};
+use tokio::net::TcpListener;
 
 use crate::networking::handle_stream;
 
-use super::InternalMessage;
+use super::{ConnectionLimiter, Event, Limits, PeerRegistry, RateLimiter, TransportConfig};
 
 pub struct Server {
-    server: Option<TcpListener>,
-    incoming_queue_sender: Sender<InternalMessage>,
-    outgoing_queue_receiver_adder: Arc<Mutex<Bus<InternalMessage>>>,
+    server: TcpListener,
+    transport: TransportConfig,
+    limits: Limits,
+    incoming_queue_sender: Sender<Event>,
+    peers: Arc<PeerRegistry>,
 }
 
 impl Server {
-    pub fn new(
+    pub async fn new(
         addr: String,
-        incoming_queue_sender: Sender<InternalMessage>,
-        outgoing_queue_receiver_adder: Arc<Mutex<Bus<InternalMessage>>>,
+        transport: TransportConfig,
+        limits: Limits,
+        incoming_queue_sender: Sender<Event>,
+        peers: Arc<PeerRegistry>,
     ) -> Result<Self> {
         Ok(Self {
-            server: Some(TcpListener::bind(addr)?),
+            server: TcpListener::bind(addr).await?,
+            transport,
+            limits,
             incoming_queue_sender,
-            outgoing_queue_receiver_adder,
+            peers,
         })
     }
 
-    pub fn start_networking(&mut self) {
-        let sender = self.incoming_queue_sender.clone();
-        let receiver_adder = self.outgoing_queue_receiver_adder.clone();
+    pub async fn start_networking(self) {
+        let connection_limiter = ConnectionLimiter::new(&self.limits);
 
-        let server = self.server.take().unwrap();
-
-        thread::spawn(move || loop {
-            match server.accept() {
+        loop {
+            match self.server.accept().await {
                 Ok((stream, socketaddr)) => {
+                    if self.peers.is_banned(socketaddr.ip()) {
+                        warn!("Rejected the connection from {}: it's banned", socketaddr);
+                        continue;
+                    }
+
+                    let slot = match connection_limiter.try_acquire(socketaddr.ip()) {
+                        Ok(slot) => slot,
+                        Err(err) => {
+                            warn!("Rejected the connection from {}: {}", socketaddr, err);
+                            continue;
+                        }
+                    };
+
                     info!("New connection from {}", socketaddr);
-                    handle_stream(
-                        stream,
-                        sender.clone(),
-                        receiver_adder.lock().unwrap().add_rx(),
-                    );
+
+                    let transport = self.transport.clone();
+                    let rate_limiter = RateLimiter::new(&self.limits);
+                    let sender = self.incoming_queue_sender.clone();
+                    let peers = self.peers.clone();
+
+                    // don't block other connections while doing the handshake
+                    tokio::spawn(async move {
+                        match transport.establish(stream, false).await {
+                            Ok(connection) => {
+                                let queue = peers.add(&connection.address);
+                                handle_stream(connection, sender, queue, peers, Some(rate_limiter))
+                                    .await;
+                            }
+                            Err(err) => {
+                                warn!("Rejected the connection from {}: {}", socketaddr, err)
+                            }
+                        }
+
+                        // the connection doesn't count anymore
+                        drop(slot);
+                    });
                 }
                 Err(err) => error!("Couldn't connect to client because of {}", err),
             }
-        });
+        }
     }
 }
@@ -0,0 +1,300 @@
+use std::{
+    fs,
+    io::{Error, ErrorKind, Result},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use log::info;
+use snow::{
+    params::DHChoice,
+    resolvers::{CryptoResolver, DefaultResolver},
+    Builder, HandshakeState, TransportState,
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{
+        tcp::{OwnedReadHalf, OwnedWriteHalf},
+        TcpStream,
+    },
+    time::timeout,
+};
+
+use crate::{
+    consts::HANDSHAKE_TIMEOUT,
+    util::{from_hex, to_hex, LogExpect},
+};
+
+const NOISE_PARAMS: &str = "Noise_XX_25519_ChaChaPoly_SHA256";
+const NOISE_MAX_MESSAGE_SIZE: usize = 65535;
+const NOISE_TAG_SIZE: usize = 16;
+const NOISE_MAX_PAYLOAD_SIZE: usize = NOISE_MAX_MESSAGE_SIZE - NOISE_TAG_SIZE;
+
+/// the static x25519 keypair a node uses to authenticate itself in the noise handshake
+#[derive(Clone)]
+pub struct NodeKey {
+    pub private_key: Vec<u8>,
+    pub public_key: Vec<u8>,
+}
+
+impl NodeKey {
+    pub fn new_random() -> Self {
+        let keypair = Builder::new(NOISE_PARAMS.parse().unwrap())
+            .generate_keypair()
+            .unwrap();
+
+        Self {
+            private_key: keypair.private,
+            public_key: keypair.public,
+        }
+    }
+
+    pub fn from_private_key(private_key: Vec<u8>) -> Result<Self> {
+        let mut dh = DefaultResolver.resolve_dh(&DHChoice::Curve25519).unwrap();
+
+        if private_key.len() != dh.priv_len() {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("a node key has to be {} bytes long", dh.priv_len()),
+            ));
+        }
+
+        dh.set(&private_key);
+
+        Ok(Self {
+            public_key: dh.pubkey().to_vec(),
+            private_key,
+        })
+    }
+
+    pub fn new_from_keyfile(node_key_file: PathBuf) -> Self {
+        let private_key_string = fs::read_to_string(&node_key_file).log_expect(&format!(
+            "Failed to read the node key from {:?}",
+            node_key_file
+        ));
+
+        let private_key = from_hex(private_key_string.trim()).log_expect(&format!(
+            "{:?} is not a hex-encoded node key file",
+            node_key_file
+        ));
+
+        Self::from_private_key(private_key)
+            .log_expect(&format!("{:?} is not a valid node key file", node_key_file))
+    }
+
+    pub fn to_string(&self) -> (String, String) {
+        (to_hex(&self.private_key), to_hex(&self.public_key))
+    }
+}
+
+/// read a list of hex-encoded node public keys, one per line (empty lines and #-comments are ignored)
+pub fn load_allowed_peers(allowed_peers_file: PathBuf) -> Vec<Vec<u8>> {
+    fs::read_to_string(&allowed_peers_file)
+        .log_expect(&format!(
+            "Failed to read the allowed peers from {:?}",
+            allowed_peers_file
+        ))
+        .lines()
+        .map(|line| line.split('#').next().unwrap().trim())
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            from_hex(line).log_expect(&format!(
+                "{:?} contains an invalid node key",
+                allowed_peers_file
+            ))
+        })
+        .collect()
+}
+
+#[derive(Clone)]
+pub enum TransportConfig {
+    Plain,
+    Noise {
+        node_key: NodeKey,
+        // if set, only peers with one of these node keys are accepted
+        allowed_peers: Option<Vec<Vec<u8>>>,
+    },
+}
+
+/// an established connection to a peer, split into its two halves
+pub struct Connection {
+    pub address: String,
+    pub reader: ConnectionReader,
+    pub writer: ConnectionWriter,
+}
+
+impl TransportConfig {
+    pub async fn establish(&self, stream: TcpStream, initiator: bool) -> Result<Connection> {
+        let address = stream.peer_addr()?.to_string();
+        let (mut read_half, mut write_half) = stream.into_split();
+
+        let noise = match self {
+            TransportConfig::Plain => None,
+            TransportConfig::Noise {
+                node_key,
+                allowed_peers,
+            } => {
+                let builder = Builder::new(NOISE_PARAMS.parse().unwrap())
+                    .local_private_key(&node_key.private_key);
+                let handshake = if initiator {
+                    builder.build_initiator()
+                } else {
+                    builder.build_responder()
+                }
+                .map_err(noise_error)?;
+
+                // a peer which stalls the handshake is dropped
+                let state = timeout(
+                    HANDSHAKE_TIMEOUT,
+                    run_handshake(handshake, &mut read_half, &mut write_half),
+                )
+                .await
+                .map_err(|_| Error::new(ErrorKind::TimedOut, "the handshake timed out"))??;
+
+                let remote_key = state.get_remote_static().unwrap_or(&[]).to_vec();
+                if let Some(allowed_peers) = allowed_peers {
+                    if !allowed_peers.contains(&remote_key) {
+                        return Err(Error::new(
+                            ErrorKind::PermissionDenied,
+                            format!("the node key {} is not allowed", to_hex(&remote_key)),
+                        ));
+                    }
+                }
+
+                info!(
+                    "Established an encrypted connection to {} with node key {}",
+                    address,
+                    to_hex(&remote_key)
+                );
+
+                Some(Arc::new(Mutex::new(state)))
+            }
+        };
+
+        Ok(Connection {
+            address,
+            reader: ConnectionReader {
+                stream: read_half,
+                noise: noise.clone(),
+                buffer: vec![],
+                position: 0,
+            },
+            writer: ConnectionWriter {
+                stream: write_half,
+                noise,
+            },
+        })
+    }
+}
+
+fn noise_error(err: snow::Error) -> Error {
+    Error::new(ErrorKind::InvalidData, err.to_string())
+}
+
+async fn write_noise_message(stream: &mut OwnedWriteHalf, message: &[u8]) -> Result<()> {
+    let mut frame = (message.len() as u16).to_be_bytes().to_vec();
+    frame.extend_from_slice(message);
+    stream.write_all(&frame).await
+}
+
+async fn read_noise_message(stream: &mut OwnedReadHalf) -> Result<Vec<u8>> {
+    let mut length = [0; 2];
+    stream.read_exact(&mut length).await?;
+
+    let mut message = vec![0; u16::from_be_bytes(length) as usize];
+    stream.read_exact(&mut message).await?;
+
+    Ok(message)
+}
+
+async fn run_handshake(
+    mut handshake: HandshakeState,
+    read_half: &mut OwnedReadHalf,
+    write_half: &mut OwnedWriteHalf,
+) -> Result<TransportState> {
+    let mut buf = vec![0; NOISE_MAX_MESSAGE_SIZE];
+
+    while !handshake.is_handshake_finished() {
+        if handshake.is_my_turn() {
+            let length = handshake
+                .write_message(&[], &mut buf)
+                .map_err(noise_error)?;
+            write_noise_message(write_half, &buf[..length]).await?;
+        } else {
+            let message = read_noise_message(read_half).await?;
+            handshake
+                .read_message(&message, &mut buf)
+                .map_err(noise_error)?;
+        }
+    }
+
+    handshake.into_transport_mode().map_err(noise_error)
+}
+
+pub struct ConnectionReader {
+    stream: OwnedReadHalf,
+    noise: Option<Arc<Mutex<TransportState>>>,
+    // decrypted bytes which weren't read yet
+    buffer: Vec<u8>,
+    position: usize,
+}
+
+impl ConnectionReader {
+    pub async fn read_exact(&mut self, buf: &mut [u8]) -> Result<()> {
+        let noise = match &self.noise {
+            Some(noise) => noise.clone(),
+            None => return self.stream.read_exact(buf).await.map(|_| ()),
+        };
+
+        let mut filled = 0;
+
+        while filled < buf.len() {
+            if self.position == self.buffer.len() {
+                let message = read_noise_message(&mut self.stream).await?;
+
+                self.buffer.resize(message.len(), 0);
+                let length = noise
+                    .lock()
+                    .unwrap()
+                    .read_message(&message, &mut self.buffer)
+                    .map_err(noise_error)?;
+                self.buffer.truncate(length);
+                self.position = 0;
+            }
+
+            let length = (buf.len() - filled).min(self.buffer.len() - self.position);
+            buf[filled..filled + length]
+                .copy_from_slice(&self.buffer[self.position..self.position + length]);
+            self.position += length;
+            filled += length;
+        }
+
+        Ok(())
+    }
+}
+
+pub struct ConnectionWriter {
+    stream: OwnedWriteHalf,
+    noise: Option<Arc<Mutex<TransportState>>>,
+}
+
+impl ConnectionWriter {
+    pub async fn write_all(&mut self, buf: &[u8]) -> Result<()> {
+        let noise = match &self.noise {
+            Some(noise) => noise.clone(),
+            None => return self.stream.write_all(buf).await,
+        };
+
+        for payload in buf.chunks(NOISE_MAX_PAYLOAD_SIZE) {
+            let mut message = vec![0; payload.len() + NOISE_TAG_SIZE];
+            let length = noise
+                .lock()
+                .unwrap()
+                .write_message(payload, &mut message)
+                .map_err(noise_error)?;
+            write_noise_message(&mut self.stream, &message[..length]).await?;
+        }
+
+        Ok(())
+    }
+}
@@ -39,3 +39,28 @@ impl<T, E: Display> LogExpect<T> for Result<T, E> {
         }
     }
 }
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+pub fn from_hex(string: &str) -> Result<Vec<u8>, String> {
+    if !string.is_ascii() || !string.len().is_multiple_of(2) {
+        return Err(format!("{:?} is not a valid hex string", string));
+    }
+
+    (0..string.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&string[i..i + 2], 16)
+                .map_err(|_| format!("{:?} is not a valid hex string", string))
+        })
+        .collect()
+}
+
+/// the sum of the amounts, None if it overflows
+pub fn checked_sum(amounts: impl IntoIterator<Item = u32>) -> Option<u32> {
+    amounts
+        .into_iter()
+        .try_fold(0u32, |sum, amount| sum.checked_add(amount))
+}